@@ -2,16 +2,21 @@ mod app_data;
 pub mod server_auth;
 
 use actix_web::{
-    get, post, web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer, Responder,
+    get, http::StatusCode, post, web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer,
+    Responder,
 };
+use chrono::Duration;
 use dotenv::dotenv;
-use fresh_eyes_api::{
-    extract_pr_details, Branch as LibBranch, ForkRequest as LibForkRequest,
-    PullRequest as LibPullRequest,
+use fresh_eyes::{
+    cleanup_mirrors, find_pull_requests_by_label, list_mirrors, mirror_pull_request,
+    mirror_pull_requests, normalize_host, refresh_mirror, reveal, BatchResult, CleanupFilter,
+    ErrorResponse, ForkRequest, FreshEyesError, GitHubClient, MirrorProgress, MirrorStyle,
+    PullRequestLocator, RefreshResult, ResolvedThreads, UpstreamState, DEFAULT_BATCH_CONCURRENCY,
+    GITHUB_HOST,
 };
 use serde::{Deserialize, Serialize};
 use server_auth::Authentication;
-use std::env;
+use std::{env, sync::Mutex};
 
 use crate::app_data::AppData;
 
@@ -19,107 +24,470 @@ use crate::app_data::AppData;
 async fn hello() -> impl Responder {
     HttpResponse::Ok().body("Hello world!")
 }
-// Define a struct to receive the pull request data
+// Define a struct to receive the pull request data, either `owner`, `repo` and `pull_number`
+// or `pull_request` with a link like https://github.com/bitcoin/bitcoin/pull/8149 or bitcoin/bitcoin#8149
 #[derive(Deserialize, Debug)]
 pub struct PullRequest {
-    owner: String,
-    repo: String,
-    pull_number: i32,
+    owner: Option<String>,
+    repo: Option<String>,
+    pull_number: Option<u32>,
+    pull_request: Option<String>,
+    /// GitHub host of the repository, github.com unless it's on a GitHub Enterprise Server.
+    /// Links carry their own host.
+    host: Option<String>,
+    /// leave the author out of the mirror, for reviews without bias
+    #[serde(default)]
+    blind: bool,
+    /// what reveal does with the threads resolved upstream: `show`, `collapse` or `hide`
+    #[serde(default)]
+    resolved_threads: ResolvedThreads,
+}
+
+impl PullRequest {
+    fn locate(self) -> Result<PullRequestLocator, FreshEyesError> {
+        let host = self.host.as_deref().unwrap_or(GITHUB_HOST);
+        match (self.pull_request, self.owner, self.repo, self.pull_number) {
+            (Some(pull_request), ..) => PullRequestLocator::parse(&pull_request, host),
+            (None, Some(owner), Some(repo), Some(pull_number)) => Ok(PullRequestLocator {
+                host: normalize_host(host)?,
+                owner,
+                repo,
+                pull_number,
+            }),
+            _ => Err(FreshEyesError::ValueUndefinedError(
+                "pull_request, or owner, repo and pull_number".to_string(),
+            )),
+        }
+    }
 }
 
 // Define a struct for the response
 #[derive(Serialize)]
 pub struct PrResponse {
     pr_url: String,
+    /// `false` when the pull request had already been mirrored
+    created: bool,
+    /// the steps taken to mirror the pull request, in order
+    progress: Vec<MirrorProgress>,
+}
+
+#[derive(Serialize)]
+pub struct RefreshResponse {
+    /// the branches before and after, `forced` tells whether upstream was force-pushed
+    #[serde(flatten)]
+    refresh: RefreshResult,
+    progress: Vec<MirrorProgress>,
+}
+
+// Define a struct to receive a batch of pull requests of one repository
+#[derive(Deserialize, Debug)]
+pub struct BatchRequest {
+    owner: String,
+    repo: String,
+    host: Option<String>,
+    /// numbers of, or links to, pull requests of the repository
+    #[serde(default)]
+    pull_requests: Vec<String>,
+    /// also mirror the open pull requests with this label
+    label: Option<String>,
+    /// how many pull requests to mirror at the same time, at most `MAX_BATCH_CONCURRENCY`
+    concurrency: Option<usize>,
+    /// leave the authors out of the mirrors
+    #[serde(default)]
+    blind: bool,
+}
+
+/// Upper bound of the concurrency of a batch, so one request can't use up the caller's rate limit
+const MAX_BATCH_CONCURRENCY: usize = 8;
+
+/// How long a request waits for GitHub to create a fork. The server doesn't hold a request for
+/// the minutes a large repository takes, it answers with 503 and the caller retries later.
+const FORK_READY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(20);
+
+#[derive(Serialize)]
+pub struct BatchStep {
+    /// `None` while forking
+    pull_number: Option<u32>,
+    #[serde(flatten)]
+    step: MirrorProgress,
+}
+
+#[derive(Serialize)]
+pub struct BatchResponse {
+    #[serde(flatten)]
+    batch: BatchResult,
+    progress: Vec<BatchStep>,
+}
+
+// Define a struct to receive the repository to list the mirrors of
+#[derive(Deserialize, Debug)]
+pub struct RepositoryQuery {
+    owner: String,
+    repo: String,
+    host: Option<String>,
+}
+
+// Define a struct to receive the cleanup request
+#[derive(Deserialize, Debug)]
+pub struct CleanupRequest {
+    owner: String,
+    repo: String,
+    host: Option<String>,
+    /// only mirrors created at least this many days ago
+    older_than_days: Option<i64>,
+    /// only mirrors whose upstream pull request is `open`, `closed` or `merged`
+    #[serde(default)]
+    upstream_states: Vec<UpstreamState>,
+    /// only the mirrors of these upstream pull requests
+    #[serde(default)]
+    pull_numbers: Vec<u64>,
+    /// only report what would be cleaned up
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Respond with GitHub's status code and explanation when a GitHub call failed,
+/// so clients can tell e.g. a missing permission apart from a missing pull request
+fn error_response(context: &str, error: FreshEyesError) -> HttpResponse {
+    let error_response = match error.error_response() {
+        Some(error_response) => ErrorResponse {
+            message: format!("{}: {}", context, error_response.message),
+            ..error_response.clone()
+        },
+        None => ErrorResponse {
+            message: format!("{}: {}", context, error),
+            status: match error {
+                FreshEyesError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS.as_u16(),
+                FreshEyesError::InvalidPullRequest(_)
+                | FreshEyesError::InvalidHost(_)
+                | FreshEyesError::ValueUndefinedError(_) => StatusCode::BAD_REQUEST.as_u16(),
+                FreshEyesError::ForkNotReady { .. } => StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+                FreshEyesError::MirrorNotFound(_) => StatusCode::NOT_FOUND.as_u16(),
+                FreshEyesError::CommitUnreachable { .. }
+                | FreshEyesError::InvalidBranchName { .. }
+                | FreshEyesError::BlindMismatch { .. } => StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+                _ => StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            },
+            errors: Vec::new(),
+            documentation_url: None,
+        },
+    };
+    let status =
+        StatusCode::from_u16(error_response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    HttpResponse::build(status).json(error_response)
+}
+
+/// The GitHub hosts callers may ask for, the server sends their tokens there.
+/// Read from the comma separated `FRESHEYES_ALLOWED_HOSTS`, github.com by default.
+#[derive(Debug, Clone)]
+struct AllowedHosts(Vec<String>);
+
+impl AllowedHosts {
+    fn from_env() -> Self {
+        let hosts = env::var("FRESHEYES_ALLOWED_HOSTS").unwrap_or_default();
+        let hosts: Vec<String> = hosts
+            .split(',')
+            .filter(|host| !host.trim().is_empty())
+            .map(|host| normalize_host(host).expect("FRESHEYES_ALLOWED_HOSTS must list hosts"))
+            .collect();
+        if hosts.is_empty() {
+            Self(vec![GITHUB_HOST.to_string()])
+        } else {
+            Self(hosts)
+        }
+    }
+
+    fn contains(&self, host: &str) -> bool {
+        normalize_host(host).is_ok_and(|host| self.0.contains(&host))
+    }
+}
+
+/// The shared client, authenticated with the caller's token and pointed at the requested host.
+/// Hosts that are not allowed are answered with 400, the token must not be sent anywhere else.
+fn github_client(
+    req: &HttpRequest,
+    github: &GitHubClient,
+    host: Option<&str>,
+) -> Result<GitHubClient, HttpResponse> {
+    let allowed_hosts = req
+        .app_data::<web::Data<AllowedHosts>>()
+        .expect("AllowedHosts not found in app data");
+    let host = host.unwrap_or(GITHUB_HOST);
+    if !allowed_hosts.contains(host) {
+        return Err(HttpResponse::BadRequest().json(ErrorResponse {
+            message: format!("Invalid host: {} is not allowed on this server", host),
+            status: StatusCode::BAD_REQUEST.as_u16(),
+            errors: Vec::new(),
+            documentation_url: None,
+        }));
+    }
+
+    let token = {
+        let extensions = req.extensions();
+        let app_data = extensions
+            .get::<AppData>()
+            .expect("AppData not found in request extensions");
+        let token = app_data.token.lock().unwrap().clone();
+        token
+    };
+    github
+        .with_host(host, token)
+        .map_err(|e| error_response("Invalid host", e))
 }
 
 // Implement the process_pull_request function
 #[post("/process_pull_request")]
-async fn process_pull_requests(req: HttpRequest, pr: web::Json<PullRequest>) -> impl Responder {
-    let extensions = req.extensions();
-    let app_data = extensions
-        .get::<AppData>()
-        .expect("AppData not found in request extensions");
-    let token = app_data.token.lock().unwrap().clone();
-
-    let pull_request = pr.into_inner();
-
-    // Create a fork of the base repository
-    let fork = LibForkRequest::new(&pull_request.owner, &pull_request.repo);
-    let fork_result = match fork.fork(token.clone()).await {
-        Ok(result) => result,
-        Err(e) => {
-            return HttpResponse::InternalServerError()
-                .body(format!("Failed to fork repository: {}", e));
-        }
+async fn process_pull_requests(
+    req: HttpRequest,
+    github: web::Data<GitHubClient>,
+    pr: web::Json<PullRequest>,
+) -> impl Responder {
+    let pr = pr.into_inner();
+    let blind = pr.blind;
+    let pull_request = match pr.locate() {
+        Ok(pull_request) => pull_request,
+        Err(e) => return error_response("Invalid pull request", e),
+    };
+    let client = match github_client(&req, &github, Some(&pull_request.host)) {
+        Ok(client) => client,
+        Err(response) => return response,
     };
 
-    // Fetch the desired pull request
-    let pull_request_instance = LibPullRequest::from_pull_number(
-        &pull_request.owner,
-        &pull_request.repo,
-        pull_request.pull_number as u32,
-    );
-    let pull_request_response = match pull_request_instance.get(token.clone()).await {
-        Ok(response) => response,
+    // Fork the repository and mirror the pull request into the fork, re-runs return the
+    // mirror the first run created. The steps are returned to the client.
+    let progress = Mutex::new(Vec::new());
+    let mirror = match mirror_pull_request(
+        &client,
+        &ForkRequest::new(&pull_request.owner, &pull_request.repo)
+            .with_ready_timeout(FORK_READY_TIMEOUT),
+        pull_request.pull_number,
+        blind,
+        MirrorStyle::Introduced,
+        &|step| progress.lock().unwrap().push(step),
+    )
+    .await
+    {
+        Ok(mirror) => mirror,
         Err(e) => {
-            return HttpResponse::InternalServerError()
-                .body(format!("Failed to fetch pull request: {}", e));
+            return error_response("Failed to mirror pull request", e);
         }
     };
 
-    let pull_request_details = extract_pr_details(&pull_request_response);
+    let pr_response = PrResponse {
+        pr_url: mirror.pr_url,
+        created: mirror.created,
+        progress: progress.into_inner().unwrap(),
+    };
+    HttpResponse::Ok().json(pr_response)
+}
+// Mirror several pull requests of a repository, forking once
+#[post("/process_pull_requests")]
+async fn process_batch(
+    req: HttpRequest,
+    github: web::Data<GitHubClient>,
+    body: web::Json<BatchRequest>,
+) -> impl Responder {
+    let request = body.into_inner();
+    let host = request.host.as_deref().unwrap_or(GITHUB_HOST);
+    let client = match github_client(&req, &github, Some(host)) {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
 
-    // Create a branch for the base repository
-    let base_branch = LibBranch::new(
-        &fork_result.owner,
-        &fork_result.repo,
-        &pull_request_details.base_ref,
-        &pull_request_details.base_sha,
-    );
-    if let Err(e) = base_branch.create(token.clone()).await {
-        return HttpResponse::InternalServerError()
-            .body(format!("Failed to create base branch: {}", e));
+    let mut pull_numbers = Vec::new();
+    for pull_request in &request.pull_requests {
+        let pull_number = match pull_request.parse() {
+            Ok(pull_number) => pull_number,
+            Err(_) => match PullRequestLocator::parse(pull_request, host) {
+                Ok(locator)
+                    if normalize_host(host).is_ok_and(|host| host == locator.host)
+                        && locator.owner.eq_ignore_ascii_case(&request.owner)
+                        && locator.repo.eq_ignore_ascii_case(&request.repo) =>
+                {
+                    locator.pull_number
+                }
+                Ok(locator) => {
+                    return error_response(
+                        "Invalid pull request",
+                        FreshEyesError::InvalidPullRequest(format!(
+                            "{}, which is not in {}/{},",
+                            locator, request.owner, request.repo
+                        )),
+                    )
+                }
+                Err(e) => return error_response("Invalid pull request", e),
+            },
+        };
+        pull_numbers.push(pull_number);
     }
-
-    // Create a branch for the head repository
-    let head_branch = LibBranch::new(
-        &fork_result.owner,
-        &fork_result.repo,
-        &pull_request_details.head_ref,
-        &pull_request_details.head_sha,
-    );
-    if let Err(e) = head_branch.create(token.clone()).await {
-        return HttpResponse::InternalServerError()
-            .body(format!("Failed to create head branch: {}", e));
+    if let Some(label) = &request.label {
+        match find_pull_requests_by_label(&client, &request.owner, &request.repo, label).await {
+            Ok(labeled) => {
+                for pull_number in labeled {
+                    if !pull_numbers.contains(&pull_number) {
+                        pull_numbers.push(pull_number);
+                    }
+                }
+            }
+            Err(e) => return error_response("Failed to find pull requests by label", e),
+        }
     }
 
-    // Create a new pull request
-    let new_pull_request = LibPullRequest::new(
-        &fork_result.owner,
-        &fork_result.repo,
-        Some(&pull_request_details.title),
-        Some(&pull_request_details.body),
-        &pull_request_details.base_ref,
-        &pull_request_details.head_ref,
-    );
-    let pull_request_result = match new_pull_request.create(token.clone()).await {
-        Ok(data) => data,
+    let concurrency = request
+        .concurrency
+        .unwrap_or(DEFAULT_BATCH_CONCURRENCY)
+        .min(MAX_BATCH_CONCURRENCY);
+    let progress = Mutex::new(Vec::new());
+    let batch = match mirror_pull_requests(
+        &client,
+        &ForkRequest::new(&request.owner, &request.repo).with_ready_timeout(FORK_READY_TIMEOUT),
+        &pull_numbers,
+        concurrency,
+        request.blind,
+        MirrorStyle::Introduced,
+        &|pull_number, step| {
+            progress
+                .lock()
+                .unwrap()
+                .push(BatchStep { pull_number, step })
+        },
+    )
+    .await
+    {
+        Ok(batch) => batch,
+        Err(e) => return error_response("Failed to mirror pull requests", e),
+    };
+
+    HttpResponse::Ok().json(BatchResponse {
+        batch,
+        progress: progress.into_inner().unwrap(),
+    })
+}
+
+// Move the branches of an existing mirror to the current state of the upstream pull request
+#[post("/refresh_pull_request")]
+async fn refresh_pull_request(
+    req: HttpRequest,
+    github: web::Data<GitHubClient>,
+    pr: web::Json<PullRequest>,
+) -> impl Responder {
+    let pr = pr.into_inner();
+    let blind = pr.blind;
+    let pull_request = match pr.locate() {
+        Ok(pull_request) => pull_request,
+        Err(e) => return error_response("Invalid pull request", e),
+    };
+    let client = match github_client(&req, &github, Some(&pull_request.host)) {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+
+    let progress = Mutex::new(Vec::new());
+    let refresh = match refresh_mirror(
+        &client,
+        &ForkRequest::new(&pull_request.owner, &pull_request.repo)
+            .with_ready_timeout(FORK_READY_TIMEOUT),
+        pull_request.pull_number,
+        blind,
+        &|step| progress.lock().unwrap().push(step),
+    )
+    .await
+    {
+        Ok(refresh) => refresh,
         Err(e) => {
-            return HttpResponse::InternalServerError()
-                .body(format!("Failed to create pull request: {}", e));
+            return error_response("Failed to refresh pull request", e);
         }
     };
 
-    // Extract the pr_url
-    let pr_url = pull_request_result["html_url"]
-        .as_str()
-        .map(String::from)
-        .unwrap_or_default();
+    HttpResponse::Ok().json(RefreshResponse {
+        refresh,
+        progress: progress.into_inner().unwrap(),
+    })
+}
+
+// Post the discussion of the upstream pull request on its mirror in the caller's fork
+#[post("/reveal_pull_request")]
+async fn reveal_pull_request(
+    req: HttpRequest,
+    github: web::Data<GitHubClient>,
+    pr: web::Json<PullRequest>,
+) -> impl Responder {
+    let pr = pr.into_inner();
+    let resolved_threads = pr.resolved_threads;
+    let pull_request = match pr.locate() {
+        Ok(pull_request) => pull_request,
+        Err(e) => return error_response("Invalid pull request", e),
+    };
+    let client = match github_client(&req, &github, Some(&pull_request.host)) {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+
+    match reveal(
+        &client,
+        &pull_request.owner,
+        &pull_request.repo,
+        pull_request.pull_number,
+        resolved_threads,
+    )
+    .await
+    {
+        Ok(reveal) => HttpResponse::Ok().json(reveal),
+        Err(e) => error_response("Failed to reveal the comments", e),
+    }
+}
 
-    let pr_response = PrResponse { pr_url };
-    HttpResponse::Ok().json(pr_response)
+// List the mirror pull requests in the caller's fork and the state of their upstream pull requests
+#[get("/mirrors")]
+async fn list(
+    req: HttpRequest,
+    github: web::Data<GitHubClient>,
+    query: web::Query<RepositoryQuery>,
+) -> impl Responder {
+    let query = query.into_inner();
+    let client = match github_client(&req, &github, query.host.as_deref()) {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+
+    match list_mirrors(&client, &query.owner, &query.repo).await {
+        Ok(mirrors) => HttpResponse::Ok().json(mirrors),
+        Err(e) => error_response("Failed to list mirrors", e),
+    }
 }
+
+// Close the mirror pull requests and delete the mirror branches in the caller's fork
+#[post("/cleanup_mirrors")]
+async fn cleanup(
+    req: HttpRequest,
+    github: web::Data<GitHubClient>,
+    body: web::Json<CleanupRequest>,
+) -> impl Responder {
+    let request = body.into_inner();
+    let client = match github_client(&req, &github, request.host.as_deref()) {
+        Ok(client) => client,
+        Err(response) => return response,
+    };
+
+    let filter = CleanupFilter {
+        older_than: request.older_than_days.map(Duration::days),
+        upstream_states: request.upstream_states,
+        pull_numbers: request.pull_numbers,
+    };
+    match cleanup_mirrors(
+        &client,
+        &request.owner,
+        &request.repo,
+        &filter,
+        request.dry_run,
+    )
+    .await
+    {
+        Ok(cleanup) => HttpResponse::Ok().json(cleanup),
+        Err(e) => error_response("Failed to clean up mirrors", e),
+    }
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok(); // load the environment variables
@@ -130,11 +498,23 @@ async fn main() -> std::io::Result<()> {
         .expect("PORT must be a number");
     println!("Server is running on port {}", port);
 
+    // one client, and so one connection pool, shared by all requests
+    let github = web::Data::new(GitHubClient::new().expect("failed to build the GitHub client"));
+    let allowed_hosts = web::Data::new(AllowedHosts::from_env());
+    println!("Allowed GitHub hosts: {}", allowed_hosts.0.join(", "));
+
     HttpServer::new(move || {
         App::new()
+            .app_data(github.clone())
+            .app_data(allowed_hosts.clone())
             .wrap(Authentication)
             .service(hello)
             .service(process_pull_requests)
+            .service(process_batch)
+            .service(refresh_pull_request)
+            .service(reveal_pull_request)
+            .service(list)
+            .service(cleanup)
     })
     .bind(("0.0.0.0", port))?
     .run()
@@ -73,7 +73,7 @@ where
             .and_then(|header| {
                 let mut parts = header.split(' ');
                 match (parts.next(), parts.next()) {
-                    (Some(scheme), Some(token)) if scheme == "Bearer" => Some(token.to_string()),
+                    (Some("Bearer"), Some(token)) => Some(token.to_string()),
                     _ => None,
                 }
             });
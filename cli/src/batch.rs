@@ -0,0 +1,125 @@
+use futures::{stream, StreamExt};
+use serde::Serialize;
+
+use crate::{
+    mirror::mirror_into_fork, ForkRequest, ForkResult, FreshEyesError, GitHubClient,
+    MirrorProgress, MirrorStyle,
+};
+
+/// How many pull requests [`mirror_pull_requests`] mirrors at the same time by default
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 4;
+
+/// The outcome of mirroring one pull request of a batch
+#[derive(Debug, Serialize)]
+pub struct BatchMirror {
+    /// number of the upstream pull request
+    pub pull_number: u32,
+    /// url of the mirror pull request, `None` when mirroring failed
+    pub pr_url: Option<String>,
+    /// `false` when an earlier run already mirrored the pull request
+    pub created: bool,
+    /// why mirroring this pull request failed
+    pub error: Option<String>,
+}
+
+/// The mirrors of a batch, in the order the pull requests were given
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    pub fork: ForkResult,
+    pub mirrors: Vec<BatchMirror>,
+}
+
+/// Mirror several pull requests of the `upstream` repository into the user's fork.
+/// The fork is created once, then up to `concurrency` pull requests are mirrored at the same time.
+/// Only a failure to fork fails the batch, every other failure is reported with its pull request.
+/// The mirrors are worded as `style` says.
+/// Progress is reported with the number of the pull request it belongs to, `None` while forking.
+/// With `blind` none of the mirrors tell who wrote their pull request.
+pub async fn mirror_pull_requests(
+    client: &GitHubClient,
+    upstream: &ForkRequest<'_>,
+    pull_numbers: &[u32],
+    concurrency: usize,
+    blind: bool,
+    style: MirrorStyle,
+    progress: &(dyn Fn(Option<u32>, MirrorProgress) + Send + Sync),
+) -> Result<BatchResult, FreshEyesError> {
+    let (owner, repo) = (upstream.owner, upstream.repo);
+    let fork = upstream
+        .fork_with_progress(client, &|step| progress(None, step))
+        .await?;
+
+    let mirrors = stream::iter(pull_numbers.iter().copied())
+        .map(|pull_number| {
+            let fork = fork.clone();
+            async move {
+                let mirror = mirror_into_fork(
+                    client,
+                    fork,
+                    owner,
+                    repo,
+                    pull_number,
+                    blind,
+                    style,
+                    &|step| progress(Some(pull_number), step),
+                )
+                .await;
+                match mirror {
+                    Ok(mirror) => BatchMirror {
+                        pull_number,
+                        pr_url: Some(mirror.pr_url),
+                        created: mirror.created,
+                        error: None,
+                    },
+                    Err(e) => BatchMirror {
+                        pull_number,
+                        pr_url: None,
+                        created: false,
+                        error: Some(e.to_string()),
+                    },
+                }
+            }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+
+    Ok(BatchResult { fork, mirrors })
+}
+
+/// Numbers of the open pull requests of `owner/repo` with the given label, e.g. `Review club`
+pub async fn find_pull_requests_by_label(
+    client: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    label: &str,
+) -> Result<Vec<u32>, FreshEyesError> {
+    // pull requests are issues, the issues endpoint can filter them by label
+    let fetch_params = format!(
+        "/repos/{}/{}/issues?state=open&labels={}",
+        owner,
+        repo,
+        encode_query_value(label)
+    );
+    let issues = client.fetch_github_data_paginated(&fetch_params).await?;
+
+    Ok(issues
+        .iter()
+        .filter(|issue| issue.get("pull_request").is_some())
+        .filter_map(|issue| issue["number"].as_u64())
+        .map(|number| number as u32)
+        .collect())
+}
+
+/// percent-encode everything but unreserved characters, labels often contain spaces or `:`
+fn encode_query_value(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
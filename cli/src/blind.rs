@@ -0,0 +1,64 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::FreshEyesError;
+
+/// The secret in `FRESHEYES_BLIND_SECRET`. Without it anyone could compute the pseudonym of
+/// every login they suspect, and changing it renames the authors of existing blind mirrors.
+pub(crate) fn blind_secret() -> Result<String, FreshEyesError> {
+    match std::env::var("FRESHEYES_BLIND_SECRET") {
+        Ok(secret) if !secret.trim().is_empty() => Ok(secret),
+        _ => Err(FreshEyesError::MissingBlindSecret),
+    }
+}
+
+/// The name a blind mirror uses for the author of `owner/repo#pull_number`.
+/// It is the same every time the pull request is mirrored or refreshed with the same `secret`,
+/// and differs between pull requests so the author can't be followed from one to the next.
+pub fn author_pseudonym(
+    secret: &str,
+    owner: &str,
+    repo: &str,
+    pull_number: u64,
+    login: &str,
+) -> String {
+    let key = format!("{}/{}#{}@{}", owner, repo, pull_number, login).to_lowercase();
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(key.as_bytes());
+    let hash = mac.finalize().into_bytes();
+    format!("author-{:02x}{:02x}{:02x}", hash[0], hash[1], hash[2])
+}
+
+/// Replace the login of the author in text about their pull request, mentioned or not,
+/// e.g. `@sipa` and `sipa` both become the pseudonym
+pub fn hide_author(text: &str, login: &str, pseudonym: &str) -> String {
+    if login.is_empty() {
+        return text.to_string();
+    }
+    let is_login_char = |c: char| c.is_ascii_alphanumeric() || c == '-';
+
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let starts_word = !output.chars().next_back().is_some_and(is_login_char);
+        let mentioned = usize::from(c == '@');
+        let matches_login = rest
+            .get(mentioned..mentioned + login.len())
+            .is_some_and(|word| word.eq_ignore_ascii_case(login));
+        let ends_word = matches_login
+            && !rest[mentioned + login.len()..]
+                .chars()
+                .next()
+                .is_some_and(is_login_char);
+        if starts_word && ends_word {
+            output.push_str(pseudonym);
+            rest = &rest[mentioned + login.len()..];
+            continue;
+        }
+
+        output.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    output
+}
@@ -0,0 +1,489 @@
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd};
+use std::ops::Range;
+
+/// What to rewrite
+#[derive(Debug, Clone, Copy, Default)]
+struct Rewrite {
+    /// `@user` and `@org/team`
+    mentions: bool,
+    /// issues, pull requests and commits
+    references: bool,
+    /// links to GitHub
+    links: bool,
+    /// HTML comments, e.g. the instructions of pull request templates
+    comments: bool,
+}
+
+/// Rewrite the body of a pull request for its mirror, so nothing upstream is notified of it
+/// and reviewers read it without links to the upstream discussion.
+/// Mentions and references are wrapped in code spans, `<code>` in HTML, links to GitHub are
+/// displayed as code and HTML comments are removed. Code, images and HTML tags are left alone,
+/// and everything else is kept byte for byte.
+pub fn modify_pull_request_body(markdown: &str) -> String {
+    rewrite(
+        markdown,
+        Rewrite {
+            mentions: true,
+            references: true,
+            links: true,
+            comments: true,
+        },
+    )
+}
+
+/// Wrap `@user` and `@org/team` mentions in code spans, so GitHub doesn't notify everyone
+/// mentioned upstream when the mirror pull request is created.
+/// Mentions in code spans and code blocks are left alone, mentions in block quotes, lists and
+/// tables are neutralized like any other.
+pub fn neutralize_mentions(markdown: &str) -> String {
+    rewrite(
+        markdown,
+        Rewrite {
+            mentions: true,
+            ..Rewrite::default()
+        },
+    )
+}
+
+/// Wrap the references GitHub autolinks in code spans, so the mirror doesn't show up in the
+/// timeline of upstream issues, pull requests and commits:
+/// `#123`, `GH-123`, `owner/repo#123`, `owner@sha`, `owner/repo@sha` and commit SHAs.
+/// References in code spans and code blocks are left alone.
+pub fn neutralize_references(markdown: &str) -> String {
+    rewrite(
+        markdown,
+        Rewrite {
+            references: true,
+            ..Rewrite::default()
+        },
+    )
+}
+
+/// [`neutralize_mentions`] and [`neutralize_references`] in one pass
+pub fn neutralize_autolinks(markdown: &str) -> String {
+    rewrite(
+        markdown,
+        Rewrite {
+            mentions: true,
+            references: true,
+            ..Rewrite::default()
+        },
+    )
+}
+
+/// Copies the markdown to the output, rewriting parts of it
+struct Rewriter<'a> {
+    markdown: &'a str,
+    rewrite: Rewrite,
+    output: String,
+    /// `markdown[..copied]` is in the output
+    copied: usize,
+    /// consecutive text events, rewritten together once a different event comes
+    text: Option<Range<usize>>,
+    /// the backticks around the code spans added, see [`code_fence`]
+    fence: String,
+}
+
+impl<'a> Rewriter<'a> {
+    fn new(markdown: &'a str, rewrite: Rewrite, fence: String) -> Self {
+        Self {
+            markdown,
+            rewrite,
+            output: String::with_capacity(markdown.len()),
+            copied: 0,
+            text: None,
+            fence,
+        }
+    }
+
+    /// `code` in a code span
+    fn code(&self, code: &str) -> String {
+        format!("{0}{1}{0}", self.fence, code)
+    }
+
+    fn copy_until(&mut self, end: usize) {
+        self.flush_text();
+        self.copy_source(end);
+    }
+
+    fn replace(&mut self, range: Range<usize>, replacement: &str) {
+        self.copy_until(range.start);
+        self.output.push_str(replacement);
+        self.copied = range.end;
+    }
+
+    fn text(&mut self, range: Range<usize>) {
+        match &mut self.text {
+            Some(text) if text.end == range.start => text.end = range.end,
+            _ => {
+                self.flush_text();
+                self.text = Some(range);
+            }
+        }
+    }
+
+    fn flush_text(&mut self) {
+        if let Some(range) = self.text.take() {
+            self.copy_source(range.start);
+            neutralize_text(
+                &self.markdown[range.clone()],
+                self.rewrite,
+                (&self.fence, &self.fence),
+                &mut self.output,
+            );
+            self.copied = range.end;
+        }
+    }
+
+    fn copy_source(&mut self, end: usize) {
+        if end > self.copied {
+            self.output.push_str(&self.markdown[self.copied..end]);
+            self.copied = end;
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.copy_until(self.markdown.len());
+        self.output
+    }
+}
+
+/// A link to GitHub being rewritten to its text followed by its url as code
+struct GitHubLink {
+    url: String,
+    /// where the link text ends so far
+    text_end: usize,
+}
+
+fn rewrite(markdown: &str, rewrite: Rewrite) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let mut rewriter = Rewriter::new(markdown, rewrite, code_fence(markdown, options));
+    // code blocks, images and HTML blocks are done with, up to here
+    let mut verbatim_until = 0;
+    let mut link: Option<GitHubLink> = None;
+
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+        if let Some(link) = &mut link {
+            if !matches!(event, Event::End(TagEnd::Link)) {
+                link.text_end = link.text_end.max(range.end);
+            }
+        }
+        // the end of a link around an image still has to be rewritten
+        if range.start < verbatim_until && range.end <= verbatim_until {
+            continue;
+        }
+
+        match event {
+            Event::Text(_) => rewriter.text(range),
+            Event::Start(Tag::CodeBlock(_) | Tag::Image { .. } | Tag::MetadataBlock(_)) => {
+                rewriter.flush_text();
+                verbatim_until = range.end;
+            }
+            Event::Start(Tag::HtmlBlock) => {
+                let html = &markdown[range.clone()];
+                let html = if rewrite.comments {
+                    strip_html_comments(html)
+                } else {
+                    html.to_string()
+                };
+                let mut neutralized = String::with_capacity(html.len());
+                // a block of only comments goes away with its line
+                if !html.trim().is_empty() {
+                    neutralize_html(&html, rewrite, &mut neutralized);
+                }
+                rewriter.replace(range.clone(), &neutralized);
+                verbatim_until = range.end;
+            }
+            Event::InlineHtml(_) if rewrite.comments => {
+                let html = strip_html_comments(&markdown[range.clone()]);
+                rewriter.replace(range, &html);
+            }
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                ..
+            }) if rewrite.links && is_github_url(&dest_url) => {
+                if matches!(link_type, LinkType::Autolink) {
+                    // <https://github.com/...>
+                    rewriter.replace(range.clone(), &rewriter.code(&dest_url));
+                    verbatim_until = range.end;
+                } else {
+                    // drop the `[`, the text is rewritten like any other
+                    rewriter.replace(range.start..range.start + 1, "");
+                    link = Some(GitHubLink {
+                        url: dest_url.to_string(),
+                        text_end: range.start + 1,
+                    });
+                }
+            }
+            Event::End(TagEnd::Link) => match link.take() {
+                Some(GitHubLink { url, text_end }) => {
+                    rewriter.copy_until(text_end);
+                    let url = format!(": ({})", rewriter.code(&url));
+                    rewriter.replace(text_end..range.end, &url);
+                }
+                None => rewriter.flush_text(),
+            },
+            _ => rewriter.flush_text(),
+        }
+    }
+
+    rewriter.finish()
+}
+
+/// The backticks around the code spans added to the text: one more than the longest run of
+/// backticks the text already has outside of code, so a stray backtick can't pair with them
+/// and turn the mention they wrap back into a live one.
+fn code_fence(markdown: &str, options: Options) -> String {
+    let longest_run = Parser::new_ext(markdown, options)
+        .into_offset_iter()
+        .filter(|(event, _)| matches!(event, Event::Text(_)))
+        .flat_map(|(_, range)| markdown[range].split(|c| c != '`').map(str::len))
+        .max()
+        .unwrap_or_default();
+    "`".repeat(longest_run + 1)
+}
+
+/// the html without its `<!-- -->` comments
+fn strip_html_comments(html: &str) -> String {
+    let mut stripped = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find("<!--") {
+        let Some(end) = rest[start..].find("-->") else {
+            break;
+        };
+        stripped.push_str(&rest[..start]);
+        rest = &rest[start + end + "-->".len()..];
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// Neutralize the autolinks in the text between the tags of an HTML block, which GitHub renders
+/// as HTML, so they are wrapped in `<code>` instead of a code span. Tags, comments and the
+/// text of `<code>`, `<pre>`, `<script>` and `<style>` elements are copied as they are.
+fn neutralize_html(html: &str, rewrite: Rewrite, output: &mut String) {
+    let mut rest = html;
+    // how many code elements the text is in
+    let mut code_depth: usize = 0;
+    while !rest.is_empty() {
+        let text_len = rest.find('<').unwrap_or(rest.len());
+        let (text, after) = rest.split_at(text_len);
+        if code_depth == 0 {
+            neutralize_text(text, rewrite, ("<code>", "</code>"), output);
+        } else {
+            output.push_str(text);
+        }
+        rest = after;
+        if rest.is_empty() {
+            break;
+        }
+
+        let tag_len = if rest.starts_with("<!--") {
+            rest.find("-->").map_or(rest.len(), |end| end + "-->".len())
+        } else {
+            rest.find('>').map_or(rest.len(), |end| end + 1)
+        };
+        let (tag, after) = rest.split_at(tag_len);
+        let name: String = tag
+            .trim_start_matches('<')
+            .trim_start_matches('/')
+            .chars()
+            .take_while(char::is_ascii_alphanumeric)
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if matches!(name.as_str(), "code" | "pre" | "script" | "style") && !tag.ends_with("/>") {
+            if tag.starts_with("</") {
+                code_depth = code_depth.saturating_sub(1);
+            } else {
+                code_depth += 1;
+            }
+        }
+        output.push_str(tag);
+        rest = after;
+    }
+}
+
+/// links to github.com, whose pull requests, issues and commits get notified of the mirror
+fn is_github_url(url: &str) -> bool {
+    let Some(rest) = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    else {
+        return false;
+    };
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    host.eq_ignore_ascii_case("github.com") || host.eq_ignore_ascii_case("www.github.com")
+}
+
+/// neutralize the autolinks of text outside of code, wrapping them in `open` and `close`
+fn neutralize_text(text: &str, rewrite: Rewrite, (open, close): (&str, &str), output: &mut String) {
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        // e-mail addresses, paths, urls, html entities and escaped characters are not autolinks
+        let preceded_by_word = output.chars().next_back().is_some_and(|c| {
+            c.is_alphanumeric() || matches!(c, '_' | '-' | '/' | '.' | '@' | '&' | '#' | '\\')
+        });
+        if !preceded_by_word {
+            if let Some(len) = autolink_len(rest, rewrite) {
+                output.push_str(open);
+                output.push_str(&rest[..len]);
+                output.push_str(close);
+                rest = &rest[len..];
+                continue;
+            }
+        }
+
+        output.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+}
+
+/// the length of the autolink the text starts with
+fn autolink_len(text: &str, rewrite: Rewrite) -> Option<usize> {
+    if rewrite.links {
+        if let Some(len) = github_url_len(text) {
+            return Some(len);
+        }
+    }
+    match text.as_bytes()[0] {
+        b'@' if rewrite.mentions => Some(1 + mention_len(&text[1..])).filter(|len| *len > 1),
+        b'#' if rewrite.references => {
+            digits_len(&text[1..]).and_then(|len| ends_at_boundary(text, 1 + len))
+        }
+        byte if rewrite.references && byte.is_ascii_alphanumeric() => gh_reference_len(text)
+            .or_else(|| repository_reference_len(text))
+            .or_else(|| sha_len(text))
+            .and_then(|len| ends_at_boundary(text, len)),
+        _ => None,
+    }
+}
+
+/// The length of the GitHub url the text starts with, which GitHub would autolink.
+/// Like GitHub, trailing punctuation and unbalanced closing parentheses aren't part of it.
+fn github_url_len(text: &str) -> Option<usize> {
+    let end = text
+        .find(|c: char| c.is_whitespace() || c == '<')
+        .unwrap_or(text.len());
+    let mut url = &text[..end];
+    if !is_github_url(url) {
+        return None;
+    }
+    loop {
+        if let Some(trimmed) =
+            url.strip_suffix(['?', '!', '.', ',', ':', ';', '*', '_', '~', '\'', '"'])
+        {
+            url = trimmed;
+        } else if url.ends_with(')') && url.matches(')').count() > url.matches('(').count() {
+            url = &url[..url.len() - 1];
+        } else {
+            return Some(url.len());
+        }
+    }
+}
+
+/// `len` if the reference isn't followed by more of a word, a file name or a path
+fn ends_at_boundary(text: &str, len: usize) -> Option<usize> {
+    let mut after = text[len..].chars();
+    let continues = match after.next() {
+        Some(c) if c.is_alphanumeric() || c == '_' => true,
+        Some('.' | '-' | '/' | '@') => after.next().is_some_and(|c| c.is_alphanumeric()),
+        _ => false,
+    };
+    (!continues).then_some(len)
+}
+
+fn digits_len(text: &str) -> Option<usize> {
+    let len = text.bytes().take_while(u8::is_ascii_digit).count();
+    (len > 0).then_some(len)
+}
+
+/// `GH-123`
+fn gh_reference_len(text: &str) -> Option<usize> {
+    let prefix = text.get(..3)?;
+    if !prefix.eq_ignore_ascii_case("GH-") {
+        return None;
+    }
+    digits_len(&text[3..]).map(|len| 3 + len)
+}
+
+/// `owner/repo#123`, `owner/repo@sha` and `owner@sha`
+fn repository_reference_len(text: &str) -> Option<usize> {
+    let owner = username_len(text);
+    if owner == 0 {
+        return None;
+    }
+    let rest = &text[owner..];
+    if let Some(sha) = rest.strip_prefix('@') {
+        return sha_len(sha).map(|len| owner + 1 + len);
+    }
+
+    let repository = rest.strip_prefix('/')?;
+    let repo = repository
+        .bytes()
+        .take_while(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'.' | b'-' | b'_'))
+        .count();
+    if repo == 0 {
+        return None;
+    }
+    let reference = &repository[repo..];
+    let len = if let Some(number) = reference.strip_prefix('#') {
+        1 + digits_len(number)?
+    } else if let Some(sha) = reference.strip_prefix('@') {
+        1 + sha_len(sha)?
+    } else {
+        return None;
+    };
+    Some(owner + 1 + repo + len)
+}
+
+/// A full 40 character commit SHA, or a short one of at least 7 characters.
+/// Short ones need a digit and a letter, so words like `defaced` and numbers stay untouched.
+fn sha_len(text: &str) -> Option<usize> {
+    let len = text.bytes().take_while(u8::is_ascii_hexdigit).count();
+    let hex = &text.as_bytes()[..len];
+    let mixed = hex.iter().any(u8::is_ascii_digit) && hex.iter().any(u8::is_ascii_alphabetic);
+    ((7..=40).contains(&len) && (len == 40 || mixed)).then_some(len)
+}
+
+/// The length of the username, or `org/team`, a mention starts with, 0 if it isn't one
+fn mention_len(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let len = username_len(text);
+    if len == 0 {
+        return 0;
+    }
+
+    // @org/team
+    if bytes.get(len) == Some(&b'/') {
+        let team = bytes[len + 1..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_'))
+            .count();
+        if team > 0 {
+            return len + 1 + team;
+        }
+    }
+    len
+}
+
+/// The length of the username the text starts with.
+/// Usernames are alphanumeric with single hyphens in between, at most 39 characters long.
+fn username_len(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let mut len = 0;
+    while len < bytes.len() && len < 39 {
+        let byte = bytes[len];
+        let hyphen_between =
+            byte == b'-' && len > 0 && bytes.get(len + 1).is_some_and(u8::is_ascii_alphanumeric);
+        if byte.is_ascii_alphanumeric() || hyphen_between {
+            len += 1;
+        } else {
+            break;
+        }
+    }
+    len
+}
@@ -0,0 +1,455 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{
+    Branch, BranchTemplate, ForkRequest, ForkResult, FreshEyesError, GitHubClient, GitReference,
+    PullRequest, PullRequestData,
+};
+
+/// State of the upstream pull request a mirror was made from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpstreamState {
+    Open,
+    /// closed without being merged
+    Closed,
+    Merged,
+    /// GitHub doesn't find it anymore, e.g. the repository was deleted or made private
+    Gone,
+}
+
+impl UpstreamState {
+    pub(crate) fn of(pull_request: Option<&PullRequestData>) -> Self {
+        match pull_request {
+            None => Self::Gone,
+            Some(pull_request) if pull_request.merged_at.is_some() => Self::Merged,
+            Some(pull_request) if pull_request.state == "closed" => Self::Closed,
+            Some(_) => Self::Open,
+        }
+    }
+}
+
+/// The upstream pull request a mirror was made from, `None` when it is gone
+pub(crate) async fn get_upstream(
+    client: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    pull_number: u64,
+) -> Result<Option<PullRequestData>, FreshEyesError> {
+    let pull_number = u32::try_from(pull_number).map_err(|_| {
+        FreshEyesError::InvalidPullRequest(format!("{}/{}#{}", owner, repo, pull_number))
+    })?;
+    match PullRequest::from_pull_number(owner, repo, pull_number)
+        .get(client)
+        .await
+    {
+        Ok(upstream) => Ok(Some(upstream)),
+        Err(FreshEyesError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Which mirrors to clean up, a mirror has to match every filter that is set
+#[derive(Debug, Clone, Default)]
+pub struct CleanupFilter {
+    /// only mirrors created at least this long ago
+    pub older_than: Option<Duration>,
+    /// only mirrors whose upstream pull request is in one of these states
+    pub upstream_states: Vec<UpstreamState>,
+    /// only mirrors of these upstream pull requests
+    pub pull_numbers: Vec<u64>,
+}
+
+/// A branch created by the mirror pipeline, `{user}-fresheyes-[staging-]{branch}-{number}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MirrorBranch<'a> {
+    /// owner of the upstream branch
+    pub user: &'a str,
+    /// name of the upstream branch
+    pub branch: &'a str,
+    /// number of the upstream pull request
+    pub pull_number: u64,
+}
+
+impl<'a> MirrorBranch<'a> {
+    /// recognize a mirror branch by the names of the default templates, see [`BranchTemplate`]
+    pub fn parse(name: &'a str) -> Option<Self> {
+        let (user, rest) = name.split_once("-fresheyes-")?;
+        let rest = rest.strip_prefix("staging-").unwrap_or(rest);
+        let (branch, pull_number) = rest.rsplit_once('-')?;
+        if user.is_empty() || branch.is_empty() {
+            return None;
+        }
+        Some(Self {
+            user,
+            branch,
+            pull_number: pull_number.parse().ok()?,
+        })
+    }
+}
+
+/// What [`cleanup_mirrors`] did, or would do on a dry run, to the mirror of one pull request
+#[derive(Debug, Serialize)]
+pub struct MirrorCleanup {
+    /// number of the upstream pull request
+    pub pull_number: u64,
+    /// `None` when fetching the upstream pull request failed, see `error`
+    pub upstream_state: Option<UpstreamState>,
+    /// the open mirror pull request in the fork, closed by the cleanup
+    pub mirror_pr_url: Option<String>,
+    /// the mirror branches in the fork, deleted by the cleanup
+    pub branches: Vec<String>,
+    /// why cleaning up this mirror failed, the others are still cleaned up
+    pub error: Option<String>,
+}
+
+/// The mirrors [`cleanup_mirrors`] found in a fork
+#[derive(Debug, Serialize)]
+pub struct CleanupResult {
+    /// `owner/repo` of the fork
+    pub fork: String,
+    /// nothing was closed or deleted
+    pub dry_run: bool,
+    pub mirrors: Vec<MirrorCleanup>,
+}
+
+/// Close the mirror pull requests and delete the mirror branches in the user's fork of
+/// `owner/repo`, for every mirror matching `filter`.
+/// With `dry_run` only report which mirrors would be cleaned up.
+/// Fails with [`FreshEyesError::MirrorNotFound`] when the user has no fork to clean up.
+pub async fn cleanup_mirrors(
+    client: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    filter: &CleanupFilter,
+    dry_run: bool,
+) -> Result<CleanupResult, FreshEyesError> {
+    let template = BranchTemplate::from_env()?;
+    let Some(fork) = ForkRequest::new(owner, repo).find(client).await? else {
+        return Err(FreshEyesError::MirrorNotFound(format!(
+            "{}/{}",
+            owner, repo
+        )));
+    };
+
+    // group the mirror branches and pull requests in the fork by upstream pull request
+    let refs = client
+        .fetch_github_data_paginated(&format!(
+            "/repos/{}/{}/git/matching-refs/heads/",
+            fork.owner, fork.repo
+        ))
+        .await?;
+    let mut mirrors: BTreeMap<u64, (Vec<String>, Option<PullRequestData>)> = BTreeMap::new();
+    for reference in refs {
+        let reference: GitReference = serde_json::from_value(reference)?;
+        let name = reference
+            .ref_name
+            .trim_start_matches("refs/heads/")
+            .to_string();
+        if let Some(branch) = template.parse(&name) {
+            mirrors.entry(branch.pull_number).or_default().0.push(name);
+        }
+    }
+    let pulls = client
+        .fetch_github_data_paginated(&format!(
+            "/repos/{}/{}/pulls?state=open",
+            fork.owner, fork.repo
+        ))
+        .await?;
+    for pull in pulls {
+        let pull: PullRequestData = serde_json::from_value(pull)?;
+        let pull_number = template
+            .parse(&pull.head.ref_name)
+            .map(|branch| branch.pull_number);
+        if let Some(pull_number) = pull_number {
+            mirrors.entry(pull_number).or_default().1 = Some(pull);
+        }
+    }
+
+    let mut result = CleanupResult {
+        fork: format!("{}/{}", fork.owner, fork.repo),
+        dry_run,
+        mirrors: Vec::new(),
+    };
+    for (pull_number, (branches, mirror_pr)) in mirrors {
+        if !filter.pull_numbers.is_empty() && !filter.pull_numbers.contains(&pull_number) {
+            continue;
+        }
+
+        let mut cleanup = MirrorCleanup {
+            pull_number,
+            upstream_state: None,
+            mirror_pr_url: mirror_pr.as_ref().map(|pr| pr.html_url.clone()),
+            branches,
+            error: None,
+        };
+        // one pull request GitHub won't show, e.g. 403, doesn't stop the cleanup of the others
+        let upstream = match get_upstream(client, owner, repo, pull_number).await {
+            Ok(upstream) => upstream,
+            Err(e) => {
+                cleanup.error = Some(e.to_string());
+                result.mirrors.push(cleanup);
+                continue;
+            }
+        };
+        let upstream_state = UpstreamState::of(upstream.as_ref());
+        cleanup.upstream_state = Some(upstream_state);
+        if !filter.upstream_states.is_empty() && !filter.upstream_states.contains(&upstream_state) {
+            continue;
+        }
+
+        if let Some(older_than) = filter.older_than {
+            // mirrors whose pull request was closed by hand only have their branches left,
+            // go by the last activity upstream then
+            let created_at = mirror_pr
+                .as_ref()
+                .map(|mirror_pr| &mirror_pr.created_at)
+                .or(upstream.as_ref().map(|upstream| &upstream.updated_at));
+            // nothing tells how old the branches of a gone pull request are, keep them
+            let Some(created_at) = created_at else {
+                continue;
+            };
+            let created_at = DateTime::parse_from_rfc3339(created_at).map_err(|e| {
+                FreshEyesError::Unknown(format!("invalid timestamp {}: {}", created_at, e))
+            })?;
+            if Utc::now().signed_duration_since(created_at) < older_than {
+                continue;
+            }
+        }
+
+        if !dry_run {
+            let removed = remove_mirror(client, &fork, mirror_pr.as_ref(), &cleanup.branches).await;
+            cleanup.error = removed.err().map(|e| e.to_string());
+        }
+        result.mirrors.push(cleanup);
+    }
+
+    Ok(result)
+}
+
+/// close the mirror pull request, then delete its branches
+async fn remove_mirror(
+    client: &GitHubClient,
+    fork: &ForkResult,
+    mirror_pr: Option<&PullRequestData>,
+    branches: &[String],
+) -> Result<(), FreshEyesError> {
+    if let Some(mirror_pr) = mirror_pr {
+        let pull_number = u32::try_from(mirror_pr.number)
+            .map_err(|_| FreshEyesError::InvalidPullRequest(mirror_pr.html_url.clone()))?;
+        PullRequest::from_pull_number(&fork.owner, &fork.repo, pull_number)
+            .close(client)
+            .await?;
+    }
+    for branch in branches {
+        Branch::new(&fork.owner, &fork.repo, branch, "")
+            .delete(client)
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{mock_existing_fork, pull_request_json, test_client};
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_mirror_branch() {
+        let cases = [
+            (
+                "gavinandresen-fresheyes-rounding-79",
+                Some(("gavinandresen", "rounding", 79)),
+            ),
+            (
+                "bitcoin-fresheyes-staging-master-79",
+                Some(("bitcoin", "master", 79)),
+            ),
+            (
+                "fanquake-fresheyes-2024-06-depends-30000",
+                Some(("fanquake", "2024-06-depends", 30000)),
+            ),
+            ("master", None),
+            ("gavinandresen-fresheyes-rounding", None),
+            ("-fresheyes-rounding-79", None),
+        ];
+        for (name, expected) in cases {
+            let parsed = MirrorBranch::parse(name)
+                .map(|branch| (branch.user, branch.branch, branch.pull_number));
+            assert_eq!(parsed, expected, "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_without_fork() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/user")
+            .with_body(r#"{"login": "fresheyes"}"#)
+            .create_async()
+            .await;
+        // a repository of the user that happens to have the name of upstream
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin")
+            .with_body(
+                r#"{"name": "bitcoin", "full_name": "fresheyes/bitcoin", "owner": {"login": "fresheyes"}, "html_url": "https://github.com/fresheyes/bitcoin"}"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/forks")
+            .match_query(mockito::Matcher::Any)
+            .with_body("[]")
+            .create_async()
+            .await;
+        let fork = server
+            .mock("POST", "/repos/bitcoin/bitcoin/forks")
+            .expect(0)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let cleanup = cleanup_mirrors(
+            &client,
+            "bitcoin",
+            "bitcoin",
+            &CleanupFilter::default(),
+            true,
+        )
+        .await;
+
+        fork.assert_async().await;
+        assert!(matches!(cleanup, Err(FreshEyesError::MirrorNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_mirrors() {
+        let mut server = mockito::Server::new_async().await;
+        mock_existing_fork(&mut server).await;
+        let reference = |name: &str| {
+            format!(
+                r#"{{"ref": "refs/heads/{}", "url": "", "object": {{"sha": "abc", "type": "commit", "url": ""}}}}"#,
+                name
+            )
+        };
+        server
+            .mock(
+                "GET",
+                "/repos/fresheyes/bitcoin-fork/git/matching-refs/heads/",
+            )
+            .match_query(mockito::Matcher::Any)
+            .with_body(format!(
+                "[{}, {}, {}, {}, {}, {}]",
+                reference("master"),
+                reference("bitcoin-fresheyes-master-79"),
+                reference("gavinandresen-fresheyes-rounding-79"),
+                reference("sipa-fresheyes-segwit-80"),
+                reference("sipa-fresheyes-gone-81"),
+                reference("sipa-fresheyes-private-82")
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin-fork/pulls")
+            .match_query(mockito::Matcher::Any)
+            .with_body("[]")
+            .create_async()
+            .await;
+        let upstream = |number: u64, state: &str, merged_at: Option<&str>| {
+            let mut upstream =
+                pull_request_json("bitcoin", "bitcoin", number, "gavinandresen", "rounding");
+            upstream["state"] = json!(state);
+            upstream["merged_at"] = json!(merged_at);
+            upstream.to_string()
+        };
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/79")
+            .with_body(upstream(79, "closed", Some("2011-02-04T00:00:00Z")))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/80")
+            .with_body(upstream(80, "open", None))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/81")
+            .with_status(404)
+            .with_body(r#"{"message": "Not Found"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/82")
+            .with_status(403)
+            .with_body(r#"{"message": "Resource not accessible by personal access token"}"#)
+            .create_async()
+            .await;
+        let deleted = server
+            .mock(
+                "DELETE",
+                mockito::Matcher::Regex(r"-fresheyes-\w+-79$".to_string()),
+            )
+            .with_status(204)
+            .expect(2)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+        let filter = CleanupFilter {
+            upstream_states: vec![UpstreamState::Merged, UpstreamState::Closed],
+            older_than: Some(chrono::Duration::days(30)),
+            ..CleanupFilter::default()
+        };
+
+        let preview = cleanup_mirrors(&client, "bitcoin", "bitcoin", &filter, true)
+            .await
+            .unwrap();
+        let cleanup = cleanup_mirrors(&client, "bitcoin", "bitcoin", &filter, false)
+            .await
+            .unwrap();
+
+        deleted.assert_async().await;
+        for result in [&preview, &cleanup] {
+            assert_eq!(result.fork, "fresheyes/bitcoin-fork");
+            assert_eq!(result.mirrors.len(), 2);
+            assert_eq!(result.mirrors[0].pull_number, 79);
+            assert_eq!(
+                result.mirrors[0].upstream_state,
+                Some(UpstreamState::Merged)
+            );
+            assert_eq!(
+                result.mirrors[0].branches,
+                [
+                    "bitcoin-fresheyes-master-79",
+                    "gavinandresen-fresheyes-rounding-79"
+                ]
+            );
+            assert!(result.mirrors[0].error.is_none());
+            // one upstream pull request GitHub won't show doesn't stop the others
+            assert_eq!(result.mirrors[1].pull_number, 82);
+            assert_eq!(result.mirrors[1].upstream_state, None);
+            assert!(result.mirrors[1]
+                .error
+                .as_deref()
+                .unwrap()
+                .contains("Resource not accessible"));
+        }
+
+        // the mirrors of pull requests GitHub doesn't find anymore
+        let filter = CleanupFilter {
+            upstream_states: vec![UpstreamState::Gone],
+            ..CleanupFilter::default()
+        };
+        let gone = cleanup_mirrors(&client, "bitcoin", "bitcoin", &filter, true)
+            .await
+            .unwrap();
+        let gone: Vec<_> = gone
+            .mirrors
+            .iter()
+            .filter(|mirror| mirror.error.is_none())
+            .map(|mirror| (mirror.pull_number, mirror.upstream_state))
+            .collect();
+        assert_eq!(gone, [(81, Some(UpstreamState::Gone))]);
+    }
+}
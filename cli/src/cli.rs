@@ -1,48 +1,362 @@
-use clap::{App, Arg};
+use chrono::Duration;
+use clap::{value_t, values_t, App, AppSettings, Arg, ArgMatches, ErrorKind, SubCommand};
+use fresh_eyes::{
+    normalize_host, CleanupFilter, PullRequestLocator, ResolvedThreads, UpstreamState,
+    DEFAULT_BATCH_CONCURRENCY,
+};
 
 /// The command line arguments
 pub struct CliArgs {
+    /// What to do
+    pub command: Command,
     /// The owner of the repository
     pub owner: String,
     /// The name of the repository
     pub repo: String,
-    /// The pull request number you want to review
-    pub pr_number: u32,
+    /// The GitHub host, e.g. github.com or a GitHub Enterprise Server host
+    pub host: String,
+}
+
+/// The subcommands
+pub enum Command {
+    /// Mirror the pull request into your fork, the default
+    Mirror {
+        /// The pull request number you want to review
+        pr_number: u32,
+        /// Leave the author out of the mirror
+        blind: bool,
+    },
+    /// Move the branches of an existing mirror to the current state of the pull request
+    Refresh { pr_number: u32, blind: bool },
+    /// Post the upstream discussion on the mirror, once you're done reviewing
+    Reveal {
+        pr_number: u32,
+        /// What to do with the threads resolved upstream
+        resolved_threads: ResolvedThreads,
+    },
+    /// Close the mirror pull requests and delete the mirror branches in your fork
+    Cleanup {
+        filter: CleanupFilter,
+        /// Only list what would be cleaned up
+        dry_run: bool,
+    },
+    /// List the mirror pull requests in your fork and the state of their upstream pull requests
+    List,
+    /// Mirror several pull requests, forking once
+    Batch {
+        pr_numbers: Vec<u32>,
+        /// Also mirror the open pull requests with this label
+        label: Option<String>,
+        /// How many pull requests to mirror at the same time
+        concurrency: usize,
+        blind: bool,
+    },
+}
+
+/// The flag of blind mirrors, which don't tell who wrote the pull request
+fn blind_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("blind")
+        .long("blind")
+        .help("Leave the author out of the mirror, so you review without knowing who wrote the pull request")
+}
+
+/// The owner and repository arguments
+fn repository_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app.arg(
+        Arg::with_name("owner")
+            .help("The owner of the repository")
+            .required(true)
+            .index(1),
+    )
+    .arg(
+        Arg::with_name("repo")
+            .help("The name of the repository")
+            .required(true)
+            .index(2),
+    )
+}
+
+/// The owner, repository and pull request number arguments,
+/// or a single pull request link or `owner/repo#number`
+fn pull_request_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app.arg(
+        Arg::with_name("owner")
+            .help("The owner of the repository, or a link to the pull request like https://github.com/bitcoin/bitcoin/pull/8149 or bitcoin/bitcoin#8149")
+            .required(true)
+            .index(1),
+    )
+    .arg(
+        Arg::with_name("repo")
+            .help("The name of the repository")
+            .requires("pr_number")
+            .index(2),
+    )
+    .arg(
+        Arg::with_name("pr_number")
+            .help("The pull request number you want to review")
+            .index(3),
+    )
 }
 
 /// Parses the command line arguments
 pub fn run_cli() -> CliArgs {
-    let args = App::new("Fresh Eyes")
-        .version("0.1.0")
-        .author("Chaincode Labs <https://chaincode.com>")
-        .about("Review pull requests with a fresh set of eyes, away from the noise of the comments section.")
-        .arg(
-            Arg::with_name("owner")
-                .help("The owner of the repository")
-                .required(true)
-                .index(1),
+    let args = pull_request_args(
+        App::new("Fresh Eyes")
+            .version("0.1.0")
+            .author("Chaincode Labs <https://chaincode.com>")
+            .about("Review pull requests with a fresh set of eyes, away from the noise of the comments section.")
+            .setting(AppSettings::SubcommandsNegateReqs)
+            .setting(AppSettings::ArgsNegateSubcommands),
+    )
+    .arg(blind_arg())
+    .arg(
+        Arg::with_name("host")
+            .long("host")
+            .takes_value(true)
+            .global(true)
+            .default_value("github.com")
+            .help("The GitHub host, e.g. github.example.com for GitHub Enterprise Server"),
+    )
+    .subcommand(
+        pull_request_args(
+            SubCommand::with_name("refresh")
+                .about("Update an existing mirror after the pull request was force-pushed or rebased"),
+        )
+        .arg(blind_arg().help("The mirror was made with --blind")),
+    )
+    .subcommand(
+        pull_request_args(
+            SubCommand::with_name("reveal")
+                .about("Post the comments of the pull request on its mirror, once you're done reviewing"),
         )
         .arg(
-            Arg::with_name("repo")
-                .help("The name of the repository")
-                .required(true)
-                .index(2),
+            Arg::with_name("resolved")
+                .long("resolved")
+                .takes_value(true)
+                .possible_values(&["show", "collapse", "hide"])
+                .default_value("show")
+                .help("Show the threads resolved upstream, collapse them to their first comment or hide them"),
+        ),
+    )
+    .subcommand(repository_args(
+        SubCommand::with_name("list")
+            .about("List the pull requests you mirrored from the repository and whether they are up to date"),
+    ))
+    .subcommand(
+        repository_args(
+            SubCommand::with_name("batch")
+                .about("Mirror several pull requests of the repository at once")
+                .after_help("Example: fresh_eyes batch bitcoin bitcoin 8149 bitcoin/bitcoin#8150 --label \"Review club\""),
         )
         .arg(
-            Arg::with_name("pr_number")
-                .help("The pull request number you want to review")
-                .required(true)
+            Arg::with_name("pull_requests")
+                .help("Numbers of, or links to, the pull requests to mirror")
+                .multiple(true)
                 .index(3),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("label")
+                .long("label")
+                .takes_value(true)
+                .help("Mirror the open pull requests with this label"),
+        )
+        .arg(
+            Arg::with_name("concurrency")
+                .long("concurrency")
+                .short("j")
+                .takes_value(true)
+                .help("How many pull requests to mirror at the same time, 4 by default"),
+        )
+        .arg(blind_arg()),
+    )
+    .subcommand(
+        repository_args(
+            SubCommand::with_name("cleanup")
+                .about("Close the mirror pull requests and delete the mirror branches in your fork of the repository"),
+        )
+        .arg(
+            Arg::with_name("older_than")
+                .long("older-than")
+                .takes_value(true)
+                .value_name("DAYS")
+                .help("Only mirrors created at least this many days ago"),
+        )
+        .arg(
+            Arg::with_name("state")
+                .long("state")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .possible_values(&["open", "closed", "merged", "gone"])
+                .help("Only mirrors whose upstream pull request is in this state, can be repeated"),
+        )
+        .arg(
+            Arg::with_name("pr")
+                .long("pr")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("PR_NUMBER")
+                .help("Only the mirror of this upstream pull request, can be repeated"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .help("List the mirrors that would be cleaned up without changing anything"),
+        ),
+    )
+    .get_matches();
+
+    match args.subcommand() {
+        ("refresh", Some(matches)) => {
+            parse_pull_request_args(matches, |pr_number| Command::Refresh {
+                pr_number,
+                blind: matches.is_present("blind"),
+            })
+        }
+        ("reveal", Some(matches)) => {
+            let resolved_threads = match matches.value_of("resolved") {
+                Some("collapse") => ResolvedThreads::Collapse,
+                Some("hide") => ResolvedThreads::Hide,
+                _ => ResolvedThreads::Show,
+            };
+            parse_pull_request_args(matches, |pr_number| Command::Reveal {
+                pr_number,
+                resolved_threads,
+            })
+        }
+        ("cleanup", Some(matches)) => parse_repository_args(
+            matches,
+            Command::Cleanup {
+                filter: parse_cleanup_filter(matches),
+                dry_run: matches.is_present("dry_run"),
+            },
+        ),
+        ("list", Some(matches)) => parse_repository_args(matches, Command::List),
+        ("batch", Some(matches)) => {
+            let pr_numbers = parse_batch_pull_requests(matches);
+            let label = matches.value_of("label").map(str::to_string);
+            if pr_numbers.is_empty() && label.is_none() {
+                argument_error(
+                    "give the pull requests to mirror or a --label",
+                    ErrorKind::MissingRequiredArgument,
+                );
+            }
+            let concurrency = match matches.value_of("concurrency") {
+                Some(_) => value_t!(matches, "concurrency", usize).unwrap_or_else(|e| e.exit()),
+                None => DEFAULT_BATCH_CONCURRENCY,
+            };
+            parse_repository_args(
+                matches,
+                Command::Batch {
+                    pr_numbers,
+                    label,
+                    concurrency,
+                    blind: matches.is_present("blind"),
+                },
+            )
+        }
+        _ => parse_pull_request_args(&args, |pr_number| Command::Mirror {
+            pr_number,
+            blind: args.is_present("blind"),
+        }),
+    }
+}
 
-    return CliArgs {
+fn parse_repository_args(args: &ArgMatches, command: Command) -> CliArgs {
+    CliArgs {
+        command,
         owner: args.value_of("owner").unwrap().to_string(),
         repo: args.value_of("repo").unwrap().to_string(),
-        pr_number: args
-            .value_of("pr_number")
-            .unwrap()
-            .parse()
-            .expect("Invalid pull request number"),
+        host: args.value_of("host").unwrap().to_string(),
+    }
+}
+
+fn parse_pull_request_args(args: &ArgMatches, command: impl Fn(u32) -> Command) -> CliArgs {
+    let host = args.value_of("host").unwrap();
+    let pull_request = match (args.value_of("repo"), args.value_of("pr_number")) {
+        (Some(repo), Some(_)) => PullRequestLocator {
+            host: host.to_string(),
+            owner: args.value_of("owner").unwrap().to_string(),
+            repo: repo.to_string(),
+            pull_number: value_t!(args, "pr_number", u32).unwrap_or_else(|e| e.exit()),
+        },
+        _ => PullRequestLocator::parse(args.value_of("owner").unwrap(), host)
+            .unwrap_or_else(|e| argument_error(&e.to_string(), ErrorKind::InvalidValue)),
     };
+
+    CliArgs {
+        command: command(pull_request.pull_number),
+        owner: pull_request.owner,
+        repo: pull_request.repo,
+        host: pull_request.host,
+    }
+}
+
+/// pull request numbers, links and `owner/repo#number` of the repository, in the given order
+fn parse_batch_pull_requests(args: &ArgMatches) -> Vec<u32> {
+    let owner = args.value_of("owner").unwrap();
+    let repo = args.value_of("repo").unwrap();
+    let host = args.value_of("host").unwrap();
+    args.values_of("pull_requests")
+        .map(|pull_requests| {
+            pull_requests
+                .map(|pull_request| {
+                    if let Ok(pr_number) = pull_request.parse() {
+                        return pr_number;
+                    }
+                    match PullRequestLocator::parse(pull_request, host) {
+                        // a link to another host would mirror the pull request of the same
+                        // number on --host
+                        Ok(locator)
+                            if normalize_host(host).is_ok_and(|host| host == locator.host)
+                                && locator.owner.eq_ignore_ascii_case(owner)
+                                && locator.repo.eq_ignore_ascii_case(repo) =>
+                        {
+                            locator.pull_number
+                        }
+                        Ok(locator) => argument_error(
+                            &format!(
+                                "{} is not a pull request of {}/{} on {}",
+                                locator, owner, repo, host
+                            ),
+                            ErrorKind::InvalidValue,
+                        ),
+                        Err(e) => argument_error(&e.to_string(), ErrorKind::InvalidValue),
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn parse_cleanup_filter(args: &ArgMatches) -> CleanupFilter {
+    CleanupFilter {
+        older_than: args.value_of("older_than").map(|_| {
+            let days = value_t!(args, "older_than", u32).unwrap_or_else(|e| e.exit());
+            Duration::days(days.into())
+        }),
+        upstream_states: args
+            .values_of("state")
+            .map(|states| {
+                states
+                    .map(|state| match state {
+                        "open" => UpstreamState::Open,
+                        "closed" => UpstreamState::Closed,
+                        "gone" => UpstreamState::Gone,
+                        _ => UpstreamState::Merged,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        pull_numbers: match args.values_of("pr") {
+            Some(_) => values_t!(args, "pr", u64).unwrap_or_else(|e| e.exit()),
+            None => Vec::new(),
+        },
+    }
+}
+
+/// Report an invalid command line the way clap reports its own errors, and exit
+fn argument_error(message: &str, kind: ErrorKind) -> ! {
+    clap::Error::with_description(message, kind).exit()
 }
@@ -0,0 +1,355 @@
+use reqwest::{
+    header::{self, HeaderMap, HeaderValue},
+    Client, Response, StatusCode,
+};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::{
+    rate_limit::{retry_decision, RateLimit, RetryDecision},
+    ErrorResponse, FreshEyesError,
+};
+
+/// Base url of the public GitHub REST API
+pub const GITHUB_API_URL: &str = "https://api.github.com";
+/// Host of the public GitHub instance
+pub const GITHUB_HOST: &str = "github.com";
+
+/// GitHub returns at most 100 items per page of a list endpoint
+const MAX_PAGE_SIZE: u32 = 100;
+
+pub enum RequestMethod {
+    GET,
+    POST(Value),
+    PATCH(Value),
+    PUT(Value),
+    DELETE,
+}
+
+impl RequestMethod {
+    /// Whether sending the request twice has the same effect as sending it once
+    fn is_idempotent(&self) -> bool {
+        !matches!(self, RequestMethod::POST(_) | RequestMethod::PATCH(_))
+    }
+}
+
+/// Settings shared by every request a [`GitHubClient`] sends
+#[derive(Debug, Clone)]
+pub struct GitHubClientConfig {
+    /// base url of the REST API, e.g. `https://api.github.com`
+    pub base_url: String,
+    pub user_agent: String,
+    /// sent as the `X-GitHub-Api-Version` header
+    pub api_version: String,
+    /// timeout of a whole request, from connecting until the body has been read
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+}
+
+impl Default for GitHubClientConfig {
+    fn default() -> Self {
+        Self {
+            base_url: GITHUB_API_URL.to_string(),
+            user_agent: "Fresh Eyes".to_string(),
+            api_version: "2022-11-28".to_string(),
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl GitHubClientConfig {
+    /// default settings for the REST API of the given host,
+    /// which is either `github.com` or a GitHub Enterprise Server instance
+    pub fn for_host(host: &str) -> Result<Self, FreshEyesError> {
+        Ok(Self {
+            base_url: api_base_url(host)?,
+            ..Self::default()
+        })
+    }
+}
+
+/// A GitHub REST API client.
+/// It is cheap to clone: clones, including the ones returned by [`GitHubClient::with_token`],
+/// share the same connection pool.
+#[derive(Debug, Clone)]
+pub struct GitHubClient {
+    http: Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl GitHubClient {
+    /// construct an unauthenticated client for `api.github.com`
+    pub fn new() -> Result<Self, FreshEyesError> {
+        Self::with_config(GitHubClientConfig::default())
+    }
+
+    /// construct an unauthenticated client from the given settings
+    pub fn with_config(config: GitHubClientConfig) -> Result<Self, FreshEyesError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("application/vnd.github+json"),
+        );
+        headers.insert(
+            "x-github-api-version",
+            HeaderValue::from_str(&config.api_version).map_err(|_| {
+                FreshEyesError::Unknown(format!("invalid api version: {}", config.api_version))
+            })?,
+        );
+
+        let http = Client::builder()
+            .user_agent(config.user_agent)
+            .default_headers(headers)
+            .timeout(config.timeout)
+            .connect_timeout(config.connect_timeout)
+            .build()?;
+
+        Ok(Self {
+            http,
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            token: None,
+        })
+    }
+
+    /// a client that authenticates every request with the given token,
+    /// sharing the connection pool of this one
+    pub fn with_token(&self, token: String) -> Self {
+        Self {
+            http: self.http.clone(),
+            base_url: self.base_url.clone(),
+            token: Some(token),
+        }
+    }
+
+    /// a client for the REST API of another host that authenticates with `token`, sharing the
+    /// connection pool of this one. The token of this client isn't carried over, it is only
+    /// valid for this host.
+    pub fn with_host(&self, host: &str, token: String) -> Result<Self, FreshEyesError> {
+        Ok(Self {
+            http: self.http.clone(),
+            base_url: api_base_url(host)?,
+            token: Some(token),
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// resolve a path like `/repos/{owner}/{repo}` against the base url.
+    /// Absolute urls, such as the ones in `Link` headers, are returned unchanged.
+    pub fn url(&self, path: &str) -> String {
+        if path.starts_with("http://") || path.starts_with("https://") {
+            path.to_string()
+        } else {
+            format!("{}/{}", self.base_url, path.trim_start_matches('/'))
+        }
+    }
+
+    /// url of the GraphQL API, `/graphql` next to the REST API on github.com and
+    /// `/api/graphql` on GitHub Enterprise Server
+    pub fn graphql_url(&self) -> String {
+        let api = self.base_url.strip_suffix("/v3").unwrap_or(&self.base_url);
+        format!("{}/graphql", api)
+    }
+
+    /// Run a GraphQL query and return its `data`.
+    /// GraphQL reports most errors in the body of a `200 OK`, they become [`FreshEyesError::GraphQL`].
+    pub async fn fetch_graphql(
+        &self,
+        query: &str,
+        variables: Value,
+    ) -> Result<Value, FreshEyesError> {
+        let body = json!({ "query": query, "variables": variables });
+        // the queries only read, sending them again after a server error is safe
+        let mut response = self
+            .fetch_github_data_with(&self.graphql_url(), RequestMethod::POST(body), true)
+            .await?;
+        if let Some(errors) = response["errors"]
+            .as_array()
+            .filter(|errors| !errors.is_empty())
+        {
+            let messages: Vec<&str> = errors
+                .iter()
+                .filter_map(|error| error["message"].as_str())
+                .collect();
+            return Err(FreshEyesError::GraphQL(messages.join(", ")));
+        }
+        Ok(response["data"].take())
+    }
+
+    /// Send a request and parse the response.
+    /// Responses without a body, like the `204 No Content` of most deletes, are `Value::Null`.
+    pub async fn fetch_github_data(
+        &self,
+        path: &str,
+        method: RequestMethod,
+    ) -> Result<Value, FreshEyesError> {
+        let idempotent = method.is_idempotent();
+        self.fetch_github_data_with(path, method, idempotent).await
+    }
+
+    /// [`GitHubClient::fetch_github_data`], retrying server errors when `idempotent`
+    async fn fetch_github_data_with(
+        &self,
+        path: &str,
+        method: RequestMethod,
+        idempotent: bool,
+    ) -> Result<Value, FreshEyesError> {
+        let response = self
+            .send_github_request(&self.url(path), method, idempotent)
+            .await?;
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(Value::Null);
+        }
+
+        let body = response.bytes().await?;
+        if body.iter().all(u8::is_ascii_whitespace) {
+            return Ok(Value::Null);
+        }
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Fetch every page of a GitHub list endpoint.
+    /// GitHub returns at most 100 items per page, so this follows the `Link: rel="next"` header
+    /// until the last page and returns the items of all pages in order.
+    pub async fn fetch_github_data_paginated(
+        &self,
+        path: &str,
+    ) -> Result<Vec<Value>, FreshEyesError> {
+        let mut items = Vec::new();
+        let mut next_url = Some(with_page_size(&self.url(path), MAX_PAGE_SIZE));
+
+        while let Some(url) = next_url {
+            let response = self
+                .send_github_request(&url, RequestMethod::GET, true)
+                .await?;
+            next_url = next_page_url(response.headers());
+
+            match response.json().await? {
+                Value::Array(page) => items.extend(page),
+                other => {
+                    return Err(FreshEyesError::Unknown(format!(
+                        "expected a list from {}, got: {}",
+                        url, other
+                    )))
+                }
+            }
+        }
+
+        Ok(items)
+    }
+
+    async fn send_github_request(
+        &self,
+        url: &str,
+        method: RequestMethod,
+        idempotent: bool,
+    ) -> Result<Response, FreshEyesError> {
+        let mut attempt = 0;
+        loop {
+            let mut request = match &method {
+                RequestMethod::GET => self.http.get(url),
+                RequestMethod::POST(body) => self.http.post(url).json(body),
+                RequestMethod::PATCH(body) => self.http.patch(url).json(body),
+                RequestMethod::PUT(body) => self.http.put(url).json(body),
+                RequestMethod::DELETE => self.http.delete(url),
+            };
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+            let response = request.send().await?;
+
+            // check the status code
+            if response.status().is_success() {
+                return Ok(response);
+            }
+
+            // back off and retry on rate limits, and on server errors when it's safe to resend.
+            // GitHub explains what went wrong in the body, e.g. a secondary rate limit
+            let status = response.status();
+            let rate_limit = RateLimit::from_headers(response.headers());
+            let body = response.text().await.unwrap_or_default();
+            match retry_decision(status, &rate_limit, &body, attempt, idempotent) {
+                RetryDecision::Retry(delay) => {
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                RetryDecision::GiveUp { reset_at } => {
+                    return Err(FreshEyesError::RateLimited {
+                        reset_at,
+                        remaining: rate_limit.remaining,
+                    });
+                }
+                RetryDecision::Fail => {
+                    return Err(FreshEyesError::from_error_response(
+                        ErrorResponse::from_response_body(status, &body),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Normalize a user supplied host, e.g. `https://GitHub.example.com/` becomes `github.example.com`.
+/// Hosts with a path, `\` or `..` are refused, the host names the file its token is saved in.
+pub fn normalize_host(host: &str) -> Result<String, FreshEyesError> {
+    let input = host.trim();
+    let host = input
+        .strip_prefix("https://")
+        .or_else(|| input.strip_prefix("http://"))
+        .unwrap_or(input);
+    let host = host.strip_suffix('/').unwrap_or(host);
+    let is_host_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | ':');
+    if host.is_empty() {
+        Ok(GITHUB_HOST.to_string())
+    } else if host.contains("..") || !host.chars().all(is_host_char) {
+        Err(FreshEyesError::InvalidHost(input.to_string()))
+    } else {
+        Ok(host.to_lowercase())
+    }
+}
+
+/// Base url of the REST API of a GitHub host.
+/// github.com serves it from `api.github.com`, GitHub Enterprise Server instances under `/api/v3`.
+pub fn api_base_url(host: &str) -> Result<String, FreshEyesError> {
+    let host = normalize_host(host)?;
+    if host == GITHUB_HOST || host == "api.github.com" {
+        Ok(GITHUB_API_URL.to_string())
+    } else {
+        Ok(format!("https://{}/api/v3", host))
+    }
+}
+
+/// Add the `per_page` query parameter to a list url unless the caller already set it
+fn with_page_size(url: &str, per_page: u32) -> String {
+    if url.contains("per_page=") {
+        return url.to_string();
+    }
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{}{}per_page={}", url, separator, per_page)
+}
+
+/// Extract the url of the next page from a `Link` header, e.g.
+/// `<https://api.github.com/...?page=2>; rel="next", <https://api.github.com/...?page=5>; rel="last"`
+pub(crate) fn next_page_url(headers: &HeaderMap) -> Option<String> {
+    let link = headers.get(header::LINK)?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let (url, params) = part.split_once(';')?;
+        let is_next = params
+            .split(';')
+            .any(|param| param.trim() == r#"rel="next""#);
+        if is_next {
+            Some(
+                url.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string(),
+            )
+        } else {
+            None
+        }
+    })
+}
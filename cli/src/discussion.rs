@@ -0,0 +1,230 @@
+use chrono::DateTime;
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    get_commit_comments, get_issue_comments, get_pull_request_commits, get_pull_request_reviews,
+    get_submitted_reviews, CommitComment, FreshEyesError, GitHubClient, IssueComment, Review,
+    ReviewComment, ReviewState, ReviewThreadState, UserFields,
+};
+
+/// How many commits [`Discussion::fetch`] fetches the comments of at the same time
+const COMMIT_COMMENTS_CONCURRENCY: usize = 4;
+
+/// Something said on a pull request
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DiscussionComment {
+    /// on the conversation
+    Issue(IssueComment),
+    /// a submitted review, with its verdict
+    Review(Review),
+    /// on a line of the diff
+    ReviewComment(Box<ReviewComment>),
+    /// on a commit of the pull request
+    Commit(CommitComment),
+}
+
+impl DiscussionComment {
+    /// when it was said, for a review when it was submitted
+    pub fn created_at(&self) -> &str {
+        match self {
+            Self::Issue(comment) => &comment.created_at,
+            Self::Review(review) => review.submitted_at.as_deref().unwrap_or_default(),
+            Self::ReviewComment(comment) => &comment.created_at,
+            Self::Commit(comment) => &comment.created_at,
+        }
+    }
+
+    /// `None` when the account has been deleted
+    pub fn user(&self) -> Option<&UserFields> {
+        match self {
+            Self::Issue(comment) => comment.user.as_ref(),
+            Self::Review(review) => review.user.as_ref(),
+            Self::ReviewComment(comment) => Some(&comment.user),
+            Self::Commit(comment) => comment.user.as_ref(),
+        }
+    }
+
+    /// empty e.g. for an approval without a comment
+    pub fn body(&self) -> &str {
+        match self {
+            Self::Issue(comment) => comment.body.as_deref().unwrap_or_default(),
+            Self::Review(review) => review.body.as_deref().unwrap_or_default(),
+            Self::ReviewComment(comment) => &comment.body,
+            Self::Commit(comment) => &comment.body,
+        }
+    }
+
+    pub fn html_url(&self) -> &str {
+        match self {
+            Self::Issue(comment) => &comment.html_url,
+            Self::Review(review) => &review.html_url,
+            Self::ReviewComment(comment) => &comment.html_url,
+            Self::Commit(comment) => &comment.html_url,
+        }
+    }
+}
+
+/// Everything said on a pull request, oldest first
+#[derive(Debug, Clone, Serialize)]
+pub struct Discussion {
+    pub comments: Vec<DiscussionComment>,
+    /// ids of the review comments in threads resolved upstream, see [`Discussion::mark_resolved`]
+    pub resolved_comments: HashSet<u64>,
+}
+
+impl Discussion {
+    /// Put the comments of a pull request in the order they were made.
+    /// Pending reviews are left out, nobody but their author can see them.
+    pub fn new(
+        review_comments: Vec<ReviewComment>,
+        issue_comments: Vec<IssueComment>,
+        reviews: Vec<Review>,
+        commit_comments: Vec<CommitComment>,
+    ) -> Self {
+        let mut comments: Vec<DiscussionComment> = review_comments
+            .into_iter()
+            .map(|comment| DiscussionComment::ReviewComment(Box::new(comment)))
+            .chain(issue_comments.into_iter().map(DiscussionComment::Issue))
+            .chain(
+                reviews
+                    .into_iter()
+                    .filter(|review| {
+                        review.state != ReviewState::Pending && review.submitted_at.is_some()
+                    })
+                    .map(DiscussionComment::Review),
+            )
+            .chain(commit_comments.into_iter().map(DiscussionComment::Commit))
+            .collect();
+        // stable, so comments made in the same second keep their order
+        comments.sort_by_key(|comment| timestamp(comment.created_at()));
+        Self {
+            comments,
+            resolved_comments: HashSet::new(),
+        }
+    }
+
+    /// The discussion of `owner/repo#pull_number`: review comments, comments on the
+    /// conversation, reviews and comments on its commits
+    pub async fn fetch(
+        client: &GitHubClient,
+        owner: &str,
+        repo: &str,
+        pull_number: u64,
+    ) -> Result<Self, FreshEyesError> {
+        let review_comments = get_pull_request_reviews(client, owner, repo, pull_number).await?;
+        let issue_comments = get_issue_comments(client, owner, repo, pull_number).await?;
+        let reviews = get_submitted_reviews(client, owner, repo, pull_number).await?;
+        let commits = get_pull_request_commits(client, owner, repo, pull_number).await?;
+        let commit_comments: Vec<Vec<CommitComment>> = stream::iter(&commits)
+            .map(|commit| get_commit_comments(client, owner, repo, &commit.sha))
+            .buffered(COMMIT_COMMENTS_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        Ok(Self::new(
+            review_comments,
+            issue_comments,
+            reviews,
+            commit_comments.into_iter().flatten().collect(),
+        ))
+    }
+
+    /// Remember which threads were resolved, the REST API the comments come from doesn't tell
+    pub fn mark_resolved(&mut self, threads: &[ReviewThreadState]) {
+        self.resolved_comments.extend(
+            threads
+                .iter()
+                .filter(|thread| thread.is_resolved)
+                .flat_map(|thread| &thread.comment_ids),
+        );
+    }
+
+    /// The review comments of the discussion, as threads, see [`ReviewThread::from_comments`]
+    pub fn review_threads(&self) -> Vec<ReviewThread> {
+        let mut threads =
+            ReviewThread::from_comments(self.comments.iter().filter_map(|comment| match comment {
+                DiscussionComment::ReviewComment(comment) => Some(comment.as_ref().clone()),
+                _ => None,
+            }));
+        for thread in &mut threads {
+            thread.resolved = self.resolved_comments.contains(&thread.root.id);
+        }
+        threads
+    }
+}
+
+/// A review comment on a line of the diff and the replies to it
+#[derive(Debug, Clone, Serialize)]
+pub struct ReviewThread {
+    pub path: String,
+    /// `None` once the line is outdated
+    pub line: Option<u64>,
+    /// `LEFT` or `RIGHT`
+    pub side: String,
+    /// the comment that started the thread
+    pub root: ReviewComment,
+    /// oldest first
+    pub replies: Vec<ReviewComment>,
+    /// resolved upstream, only known for threads of a [`Discussion`] marked with
+    /// [`Discussion::mark_resolved`]
+    pub resolved: bool,
+}
+
+impl ReviewThread {
+    /// Put every reply in the thread of the comment it replies to, ordered by path, line and side,
+    /// then by when the thread started. A reply to a deleted comment starts a thread of its own.
+    pub fn from_comments(comments: impl IntoIterator<Item = ReviewComment>) -> Vec<Self> {
+        let mut comments: Vec<ReviewComment> = comments.into_iter().collect();
+        comments.sort_by_key(|comment| (timestamp(&comment.created_at), comment.id));
+        let replied_to: HashMap<u64, Option<u64>> = comments
+            .iter()
+            .map(|comment| (comment.id, comment.in_reply_to_id))
+            .collect();
+        // GitHub replies to the first comment of a thread, but follow replies to replies anyway
+        let root_of = |mut id: u64| {
+            for _ in 0..replied_to.len() {
+                match replied_to.get(&id) {
+                    Some(Some(parent)) if replied_to.contains_key(parent) => id = *parent,
+                    _ => break,
+                }
+            }
+            id
+        };
+
+        let mut threads: Vec<Self> = Vec::new();
+        let mut thread_of_root: HashMap<u64, usize> = HashMap::new();
+        for comment in comments {
+            let root = root_of(comment.id);
+            match thread_of_root.get(&root) {
+                Some(&thread) => threads[thread].replies.push(comment),
+                None => {
+                    thread_of_root.insert(root, threads.len());
+                    threads.push(Self {
+                        path: comment.path.clone(),
+                        line: comment.line,
+                        side: comment.side.clone(),
+                        root: comment,
+                        replies: Vec::new(),
+                        resolved: false,
+                    });
+                }
+            }
+        }
+        // stable, so threads on the same line stay in the order they started
+        threads.sort_by(|a, b| (&a.path, a.line, &a.side).cmp(&(&b.path, b.line, &b.side)));
+        threads
+    }
+
+    /// the root comment, then the replies
+    pub fn comments(&self) -> impl Iterator<Item = &ReviewComment> {
+        std::iter::once(&self.root).chain(&self.replies)
+    }
+}
+
+/// seconds since the epoch, for ordering
+pub(crate) fn timestamp(time: &str) -> i64 {
+    DateTime::parse_from_rfc3339(time).map_or(0, |time| time.timestamp())
+}
@@ -0,0 +1,257 @@
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use thiserror::Error;
+
+/// An entry of the `errors` array GitHub sends with validation failures
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GitHubErrorDetail {
+    pub resource: Option<String>,
+    pub field: Option<String>,
+    /// e.g. `missing`, `invalid`, `already_exists` or `custom`
+    pub code: Option<String>,
+    pub message: Option<String>,
+}
+
+impl fmt::Display for GitHubErrorDetail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(message) = &self.message {
+            return write!(f, "{}", message);
+        }
+        let code = self.code.as_deref().unwrap_or("invalid");
+        match (&self.resource, &self.field) {
+            (Some(resource), Some(field)) => write!(f, "{} {} of {}", code, field, resource),
+            (Some(resource), None) => write!(f, "{} {}", code, resource),
+            (None, Some(field)) => write!(f, "{} {}", code, field),
+            (None, None) => write!(f, "{}", code),
+        }
+    }
+}
+
+/// The body GitHub sends with an error response
+#[derive(Debug, Deserialize)]
+struct GitHubErrorBody {
+    message: Option<String>,
+    #[serde(default)]
+    errors: Vec<Value>,
+    documentation_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorResponse {
+    pub message: String,
+    pub status: u16,
+    /// details of a validation failure, e.g. "Reference already exists"
+    pub errors: Vec<GitHubErrorDetail>,
+    /// the GitHub docs page of the endpoint that failed
+    pub documentation_url: Option<String>,
+}
+
+impl ErrorResponse {
+    /// Build an error from the status and body of a failed GitHub response.
+    /// Bodies that are not GitHub's usual JSON error, e.g. the HTML page of a proxy, are left out,
+    /// they are not meant for the user.
+    pub fn from_response_body(status: StatusCode, body: &str) -> Self {
+        match serde_json::from_str::<GitHubErrorBody>(body) {
+            Ok(error_body) => Self {
+                message: error_body
+                    .message
+                    .unwrap_or_else(|| status.canonical_reason().unwrap_or_default().to_string()),
+                status: status.as_u16(),
+                errors: error_body
+                    .errors
+                    .into_iter()
+                    .map(|error| match error {
+                        // some endpoints send plain strings instead of objects
+                        Value::String(message) => GitHubErrorDetail {
+                            message: Some(message),
+                            ..GitHubErrorDetail::default()
+                        },
+                        other => serde_json::from_value(other).unwrap_or_default(),
+                    })
+                    .collect(),
+                documentation_url: error_body.documentation_url,
+            },
+            Err(_) => Self {
+                message: status
+                    .canonical_reason()
+                    .unwrap_or("Unexpected response from GitHub")
+                    .to_string(),
+                status: status.as_u16(),
+                errors: Vec::new(),
+                documentation_url: None,
+            },
+        }
+    }
+
+    /// whether GitHub rejected a create call because the resource is already there,
+    /// e.g. "Reference already exists" or "A pull request already exists for user:branch."
+    pub fn is_already_exists(&self) -> bool {
+        self.errors.iter().any(|error| {
+            error.code.as_deref() == Some("already_exists")
+                || error
+                    .message
+                    .as_deref()
+                    .is_some_and(|message| message.contains("already exists"))
+        }) || self.message.contains("already exists")
+    }
+
+    /// whether GitHub refused to move a branch because the new commit doesn't descend from the
+    /// old one, "Update is not a fast forward"
+    pub fn is_not_fast_forward(&self) -> bool {
+        let is_not_fast_forward = |message: &str| {
+            let message = message.to_lowercase().replace('-', " ");
+            message.contains("not a fast forward")
+        };
+        self.status == StatusCode::UNPROCESSABLE_ENTITY.as_u16()
+            && (is_not_fast_forward(&self.message)
+                || self
+                    .errors
+                    .iter()
+                    .filter_map(|error| error.message.as_deref())
+                    .any(is_not_fast_forward))
+    }
+}
+
+impl fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (HTTP {})", self.message, self.status)?;
+        if !self.errors.is_empty() {
+            let errors: Vec<String> = self.errors.iter().map(|e| e.to_string()).collect();
+            write!(f, ": {}", errors.join("; "))?;
+        }
+        if let Some(documentation_url) = &self.documentation_url {
+            write!(f, ", see {}", documentation_url)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ErrorResponse {}
+
+#[derive(Error, Debug)]
+pub enum FreshEyesError {
+    #[error("could not reach GitHub: {0}")]
+    Network(#[from] reqwest::Error),
+    #[error("value of {0} is undefined")]
+    ValueUndefinedError(String),
+    /// 401, the token is missing, expired or revoked
+    #[error("unauthorized: {0}")]
+    Unauthorized(#[source] ErrorResponse),
+    /// 403, the token lacks a permission or scope
+    #[error("forbidden: {0}")]
+    Forbidden(#[source] ErrorResponse),
+    /// 404, GitHub also answers 404 for private resources the token can't see
+    #[error("not found: {0}")]
+    NotFound(#[source] ErrorResponse),
+    /// 409, e.g. the repository is empty or still being created
+    #[error("conflict: {0}")]
+    Conflict(#[source] ErrorResponse),
+    /// 422, GitHub refused the request body
+    #[error("validation failed: {0}")]
+    Validation(#[source] ErrorResponse),
+    /// any other unsuccessful status
+    #[error("{0}")]
+    StatusCodeError(#[source] ErrorResponse),
+    #[error("error forking the repository: {0}")]
+    ForkError(#[source] Box<FreshEyesError>),
+    /// GitHub was still creating the fork when [`crate::ForkRequest`] stopped waiting
+    #[error("the fork {fork} was not ready after {waited_secs} seconds, try again later")]
+    ForkNotReady { fork: String, waited_secs: u64 },
+    /// the fork can't reach a commit of the pull request, not even through upstream
+    #[error("commit {sha} is not reachable in {fork}: {reason}")]
+    CommitUnreachable {
+        sha: String,
+        fork: String,
+        reason: String,
+    },
+    #[error("{0} is not a pull request, expected a link like https://github.com/owner/repo/pull/123 or owner/repo#123")]
+    InvalidPullRequest(String),
+    /// a host that is not just a host name, e.g. with a path, which would end up in file paths
+    #[error("{0} is not a GitHub host, expected e.g. github.com or github.example.com")]
+    InvalidHost(String),
+    /// a mirror branch name git would refuse, see [`crate::check_ref_format`]
+    #[error("{name} is not a valid branch name: {reason}")]
+    InvalidBranchName { name: String, reason: String },
+    #[error("invalid branch template {template}: {reason}")]
+    InvalidBranchTemplate { template: String, reason: String },
+    /// the pull request, e.g. `bitcoin/bitcoin#8149`, has no open mirror in the user's fork
+    #[error("{0} has no mirror in your fork, mirror it first")]
+    MirrorNotFound(String),
+    /// refreshing a mirror with the other `blind` than it was made with, which names the head
+    /// branch after someone else
+    #[error(
+        "the mirror of {mirror} was made {}, refresh it the same way",
+        if *.blind { "with --blind" } else { "without --blind" }
+    )]
+    BlindMismatch {
+        mirror: String,
+        /// what the mirror was made with
+        blind: bool,
+    },
+    /// blind mirrors derive the pseudonyms of authors from `FRESHEYES_BLIND_SECRET`
+    #[error("set FRESHEYES_BLIND_SECRET to mirror blind, the pseudonyms of authors depend on it")]
+    MissingBlindSecret,
+    #[error("authorization token not found")]
+    MissingTokenError,
+    #[error("GitHub API rate limit exceeded, try again after {reset_at} (unix time)")]
+    RateLimited {
+        /// unix time (seconds) at which requests will be accepted again
+        reset_at: u64,
+        /// requests left in the current rate limit window, as reported by GitHub
+        remaining: Option<u64>,
+    },
+    #[error("unexpected response from GitHub: {0}")]
+    InvalidResponse(#[from] serde_json::Error),
+    /// the errors of a GraphQL query, which GitHub answers with `200 OK`
+    #[error("GitHub GraphQL API error: {0}")]
+    GraphQL(String),
+    #[error("unknown error: {0}")]
+    Unknown(String),
+}
+
+impl FreshEyesError {
+    /// classify a failed GitHub response by its status code
+    pub fn from_error_response(error_response: ErrorResponse) -> Self {
+        match StatusCode::from_u16(error_response.status) {
+            Ok(StatusCode::UNAUTHORIZED) => Self::Unauthorized(error_response),
+            Ok(StatusCode::FORBIDDEN) => Self::Forbidden(error_response),
+            Ok(StatusCode::NOT_FOUND) => Self::NotFound(error_response),
+            Ok(StatusCode::CONFLICT) => Self::Conflict(error_response),
+            Ok(StatusCode::UNPROCESSABLE_ENTITY) => Self::Validation(error_response),
+            _ => Self::StatusCodeError(error_response),
+        }
+    }
+
+    /// the GitHub response that caused this error, if there is one
+    pub fn error_response(&self) -> Option<&ErrorResponse> {
+        match self {
+            Self::Unauthorized(error_response)
+            | Self::Forbidden(error_response)
+            | Self::NotFound(error_response)
+            | Self::Conflict(error_response)
+            | Self::Validation(error_response)
+            | Self::StatusCodeError(error_response) => Some(error_response),
+            Self::ForkError(source) => source.error_response(),
+            _ => None,
+        }
+    }
+}
+
+/// The result of a call that creates something on GitHub which may already be there
+#[derive(Debug)]
+pub enum CreateOutcome<T> {
+    Created(T),
+    /// GitHub's explanation of what already exists
+    AlreadyExists(ErrorResponse),
+}
+
+impl<T> CreateOutcome<T> {
+    pub fn created(self) -> Option<T> {
+        match self {
+            Self::Created(value) => Some(value),
+            Self::AlreadyExists(_) => None,
+        }
+    }
+}
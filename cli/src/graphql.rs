@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{FreshEyesError, GitHubClient};
+
+/// The review threads of a pull request, 100 at a time.
+/// The REST API can't tell whether a thread was resolved, only the GraphQL API can.
+const REVIEW_THREADS_QUERY: &str = r#"
+query ($owner: String!, $repo: String!, $number: Int!, $after: String) {
+  repository(owner: $owner, name: $repo) {
+    pullRequest(number: $number) {
+      reviewThreads(first: 100, after: $after) {
+        pageInfo { hasNextPage endCursor }
+        nodes {
+          isResolved
+          isOutdated
+          comments(first: 100) { nodes { databaseId } }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// Whether a review thread was resolved upstream, and the comments it is made of
+#[derive(Debug, Clone, Serialize)]
+pub struct ReviewThreadState {
+    pub is_resolved: bool,
+    /// the line is gone since the pull request was rebased or force-pushed
+    pub is_outdated: bool,
+    /// ids of the comments, the same as in the REST API
+    pub comment_ids: Vec<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReviewThreadsPage {
+    page_info: PageInfo,
+    nodes: Vec<ReviewThreadNode>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReviewThreadNode {
+    is_resolved: bool,
+    is_outdated: bool,
+    comments: Nodes<CommentNode>,
+}
+
+#[derive(Deserialize)]
+struct Nodes<T> {
+    nodes: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommentNode {
+    /// `None` for comments of deleted accounts
+    database_id: Option<u64>,
+}
+
+/// the review threads of a pull request and whether they were resolved
+pub async fn get_review_thread_states(
+    client: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    pull_number: u64,
+) -> Result<Vec<ReviewThreadState>, FreshEyesError> {
+    let mut threads = Vec::new();
+    let mut after: Option<String> = None;
+    loop {
+        let mut data = client
+            .fetch_graphql(
+                REVIEW_THREADS_QUERY,
+                json!({
+                    "owner": owner,
+                    "repo": repo,
+                    "number": pull_number,
+                    "after": after,
+                }),
+            )
+            .await?;
+        let page = data["repository"]["pullRequest"]["reviewThreads"].take();
+        if page.is_null() {
+            return Err(FreshEyesError::Unknown(format!(
+                "no review threads in the GraphQL response for {}/{}#{}",
+                owner, repo, pull_number
+            )));
+        }
+        let page: ReviewThreadsPage = serde_json::from_value(page)?;
+
+        threads.extend(page.nodes.into_iter().map(|thread| {
+            ReviewThreadState {
+                is_resolved: thread.is_resolved,
+                is_outdated: thread.is_outdated,
+                comment_ids: thread
+                    .comments
+                    .nodes
+                    .into_iter()
+                    .filter_map(|comment| comment.database_id)
+                    .collect(),
+            }
+        }));
+        match page.page_info {
+            PageInfo {
+                has_next_page: true,
+                end_cursor: Some(end_cursor),
+            } => after = Some(end_cursor),
+            _ => return Ok(threads),
+        }
+    }
+}
@@ -1,16 +1,74 @@
-use reqwest::{
-    header::{self, HeaderMap, AUTHORIZATION},
-    Client, StatusCode,
-};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::fmt;
-use thiserror::Error;
+use std::time::{Duration, Instant};
+
+mod batch;
+mod blind;
+mod body;
+mod cleanup;
+mod client;
+mod discussion;
+mod error;
+mod graphql;
+mod list;
+mod locator;
+mod mirror;
+mod models;
+mod naming;
+use models::required;
+mod rate_limit;
+mod reveal;
 mod utils;
+pub use batch::{
+    find_pull_requests_by_label, mirror_pull_requests, BatchMirror, BatchResult,
+    DEFAULT_BATCH_CONCURRENCY,
+};
+pub use blind::{author_pseudonym, hide_author};
+pub use body::{
+    modify_pull_request_body, neutralize_autolinks, neutralize_mentions, neutralize_references,
+};
+pub use cleanup::{
+    cleanup_mirrors, CleanupFilter, CleanupResult, MirrorBranch, MirrorCleanup, UpstreamState,
+};
+pub use client::{
+    api_base_url, normalize_host, GitHubClient, GitHubClientConfig, RequestMethod, GITHUB_API_URL,
+    GITHUB_HOST,
+};
+pub use discussion::{Discussion, DiscussionComment, ReviewThread};
+pub use error::{CreateOutcome, ErrorResponse, FreshEyesError, GitHubErrorDetail};
+pub use graphql::{get_review_thread_states, ReviewThreadState};
+pub use list::{list_mirrors, MirrorStatus};
+pub use locator::PullRequestLocator;
+pub use mirror::{
+    mirror_pull_request, refresh_mirror, BranchRefresh, MirrorProgress, MirrorResult, RefUpdate,
+    RefreshResult,
+};
+pub use models::{
+    CommitComment, GitObject, GitReference, IssueComment, PullRequestCommit, PullRequestData,
+    PullRequestRef, Repository, Review, ReviewState,
+};
+pub use naming::{
+    check_ref_format, BranchTemplate, DEFAULT_BRANCH_TEMPLATE, MAX_BRANCH_NAME_LEN,
+    STAGING_BRANCH_TEMPLATE,
+};
+pub use rate_limit::RateLimit;
+pub use reveal::{plan_reveal, reveal, ResolvedThreads, RevealComment, RevealResult, RevealedLine};
+
+/// How long [`ForkRequest::fork`] waits for GitHub to create a fork, large repositories take minutes
+pub const FORK_READY_TIMEOUT: Duration = Duration::from_secs(300);
+/// How often [`ForkRequest::fork`] checks whether the fork is ready
+pub const FORK_POLL_INTERVAL: Duration = Duration::from_secs(2);
+pub use utils::get_or_prompt_token;
 
-pub enum RequestMethod {
-    GET,
-    POST(Value),
+/// How [`extract_pr_details`] words the mirror of a pull request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MirrorStyle {
+    /// the title and body of the pull request as they are, for the reviewer who mirrors it
+    #[default]
+    Plain,
+    /// a `[FreshEyes]` title and a body that introduces the author and the upstream pull
+    /// request, for the mirrors the server opens for its users
+    Introduced,
 }
 
 #[derive(Debug)]
@@ -51,9 +109,15 @@ pub struct Branch<'a> {
 pub struct ForkRequest<'a> {
     pub owner: &'a str,
     pub repo: &'a str,
+    /// how long to wait for GitHub to finish creating the fork
+    #[serde(skip)]
+    pub ready_timeout: Duration,
+    /// how long to wait between two checks of the fork
+    #[serde(skip)]
+    pub poll_interval: Duration,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ForkResult {
     pub owner: String,
     pub repo: String,
@@ -70,17 +134,34 @@ impl ForkResult {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl From<Repository> for ForkResult {
+    fn from(fork: Repository) -> Self {
+        Self::new(fork.owner.login, fork.name, fork.html_url)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UserFields {
     pub login: String,
+    /// `User`, `Bot` or `Organization`
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub user_type: Option<String>,
 }
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReviewComment {
     pub id: u64,
+    /// the review the comment was made in
+    pub pull_request_review_id: Option<u64>,
+    /// the first comment of the thread this one replies to
+    pub in_reply_to_id: Option<u64>,
     pub body: String,
     #[serde(rename = "commit_id")]
     pub commit_id: String,
     pub path: String,
+    /// the lines of the diff the comment is on, and those above them
+    #[serde(default)]
+    pub diff_hunk: String,
+    /// `None` once the line is outdated
     pub line: Option<u64>,
     #[serde(rename = "start_line")]
     pub start_line: Option<u64>,
@@ -102,34 +183,6 @@ pub struct ReviewComment {
     pub updated_at: String,
     pub user: UserFields,
 }
-#[derive(Debug, Serialize)]
-pub struct ErrorResponse {
-    pub message: String,
-    pub status: u16,
-}
-
-impl fmt::Display for ErrorResponse {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
-    }
-}
-
-#[derive(Error, Debug)]
-pub enum FreshEyesError {
-    #[error("An error occurred while the request was being executed.")]
-    RequestError(#[from] reqwest::Error),
-    #[error("value of {0} is undefined")]
-    ValueUndefinedError(String),
-    #[error("{0}")]
-    StatusCodeError(ErrorResponse),
-    #[error("error forking the repository: {0}")]
-    ForkError(String),
-    #[error("authorization token not found")]
-    MissingTokenError,
-    #[error("unknown error: {0}")]
-    Unknown(String),
-}
-
 impl<'a> PullRequest<'a> {
     /// construct pull request params to create a pull request
     pub fn new(
@@ -165,7 +218,10 @@ impl<'a> PullRequest<'a> {
     }
 
     /// create a pull request
-    pub async fn create(&self) -> Result<Value, FreshEyesError> {
+    pub async fn create(
+        &self,
+        client: &GitHubClient,
+    ) -> Result<CreateOutcome<PullRequestData>, FreshEyesError> {
         // ensure that the base and head are not None
         if self.base.is_none() || self.head.is_none() {
             return Err(FreshEyesError::ValueUndefinedError(format!(
@@ -174,10 +230,7 @@ impl<'a> PullRequest<'a> {
             )));
         }
 
-        let fetch_params = format!(
-            "https://api.github.com/repos/{}/{}/pulls",
-            self.owner, self.repo
-        );
+        let fetch_params = format!("/repos/{}/{}/pulls", self.owner, self.repo);
 
         let value = json!({
             "title": self.title,
@@ -186,31 +239,23 @@ impl<'a> PullRequest<'a> {
             "head": self.head
         });
 
-        let response = fetch_github_data(&fetch_params, RequestMethod::POST(value)).await;
-        // hceck if pull request already exists
-        return match response {
-            Ok(data) => Ok::<Value, FreshEyesError>(data),
-            Err(e) => match e {
-                FreshEyesError::StatusCodeError(error_response) => {
-                    if error_response.status == StatusCode::UNPROCESSABLE_ENTITY.as_u16() {
-                        let base = self.base.unwrap_or_default();
-                        let head = self.head.unwrap_or_default();
-                        let json_message = json!({
-                            "message": format!("A pull request already exists for {:?}<-->{:?}", base, head),
-                            "status": 422
-                        });
-                        Ok(json_message)
-                    } else {
-                        Err(FreshEyesError::StatusCodeError(error_response))
-                    }
-                }
-                _ => Err(FreshEyesError::Unknown(format!("unknown error: {:?}", e))),
-            },
-        };
+        let response = client
+            .fetch_github_data(&fetch_params, RequestMethod::POST(value))
+            .await;
+        // check if pull request already exists
+        match response {
+            Ok(data) => Ok(CreateOutcome::Created(serde_json::from_value(data)?)),
+            Err(FreshEyesError::Validation(error_response))
+                if error_response.is_already_exists() =>
+            {
+                Ok(CreateOutcome::AlreadyExists(error_response))
+            }
+            Err(e) => Err(e),
+        }
     }
 
     /// get a pull request by its number
-    pub async fn get(&self) -> Result<Value, FreshEyesError> {
+    pub async fn get(&self, client: &GitHubClient) -> Result<PullRequestData, FreshEyesError> {
         if self.pull_number.is_none() {
             return Err(FreshEyesError::ValueUndefinedError(format!(
                 "{:?}",
@@ -218,66 +263,225 @@ impl<'a> PullRequest<'a> {
             )));
         }
         let fetch_params = format!(
-            "https://api.github.com/repos/{}/{}/pulls/{:?}",
+            "/repos/{}/{}/pulls/{:?}",
             self.owner,
             self.repo,
             self.pull_number.unwrap()
         );
-        let response = fetch_github_data(&fetch_params, RequestMethod::GET).await;
-        return match response {
-            Ok(data) => Ok::<Value, FreshEyesError>(data),
-            Err(e) => {
-                if let FreshEyesError::StatusCodeError(error_response) = e {
-                    return if error_response.status == StatusCode::NOT_FOUND.as_u16() {
-                        Err(FreshEyesError::StatusCodeError(ErrorResponse {
-                            message: "pull request not found!".to_string(),
-                            status: error_response.status,
-                        }))
-                    } else {
-                        Err(FreshEyesError::StatusCodeError(error_response))
-                    };
-                }
-                Err(FreshEyesError::Unknown(format!("unknown error: {:?}", e)))
+        let response = client
+            .fetch_github_data(&fetch_params, RequestMethod::GET)
+            .await;
+        match response {
+            Ok(data) => Ok(serde_json::from_value(data)?),
+            Err(FreshEyesError::NotFound(error_response)) => {
+                Err(FreshEyesError::NotFound(ErrorResponse {
+                    message: "pull request not found!".to_string(),
+                    ..error_response
+                }))
             }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// close the pull request
+    pub async fn close(&self, client: &GitHubClient) -> Result<PullRequestData, FreshEyesError> {
+        let Some(pull_number) = self.pull_number else {
+            return Err(FreshEyesError::ValueUndefinedError(
+                "pull_number".to_string(),
+            ));
         };
+        let fetch_params = format!("/repos/{}/{}/pulls/{}", self.owner, self.repo, pull_number);
+
+        let data = client
+            .fetch_github_data(
+                &fetch_params,
+                RequestMethod::PATCH(json!({ "state": "closed" })),
+            )
+            .await?;
+        Ok(serde_json::from_value(data)?)
+    }
+
+    /// find the open pull request for the base and head, e.g. the one an earlier run created
+    pub async fn find_open(
+        &self,
+        client: &GitHubClient,
+    ) -> Result<Option<PullRequestData>, FreshEyesError> {
+        let (Some(base), Some(head)) = (self.base, self.head) else {
+            return Err(FreshEyesError::ValueUndefinedError(format!(
+                "{:?} {:?}",
+                self.base, self.head
+            )));
+        };
+        // head is namespaced by the owner of the branch, which is the owner of the repository
+        let fetch_params = format!(
+            "/repos/{}/{}/pulls?state=open&head={}:{}&base={}",
+            self.owner, self.repo, self.owner, head, base
+        );
+        let pull_requests = client.fetch_github_data_paginated(&fetch_params).await?;
+
+        match pull_requests.into_iter().next() {
+            Some(data) => Ok(Some(serde_json::from_value(data)?)),
+            None => Ok(None),
+        }
     }
 }
 
 impl<'a> ForkRequest<'a> {
     pub fn new(owner: &'a str, repo: &'a str) -> Self {
-        Self { owner, repo }
+        Self {
+            owner,
+            repo,
+            ready_timeout: FORK_READY_TIMEOUT,
+            poll_interval: FORK_POLL_INTERVAL,
+        }
     }
 
-    /// create a fork
-    pub async fn fork(&self) -> Result<ForkResult, FreshEyesError> {
-        let fetch_params = format!(
-            "https://api.github.com/repos/{}/{}/forks",
-            self.owner, self.repo
-        );
+    /// wait at most `timeout` for GitHub to create the fork instead of [`FORK_READY_TIMEOUT`]
+    pub fn with_ready_timeout(self, timeout: Duration) -> Self {
+        Self {
+            ready_timeout: timeout,
+            ..self
+        }
+    }
+
+    /// create a fork and wait until it can be used
+    pub async fn fork(&self, client: &GitHubClient) -> Result<ForkResult, FreshEyesError> {
+        self.fork_with_progress(client, &|_| {}).await
+    }
+
+    /// create a fork and wait until it can be used, reporting each step to `progress`
+    pub async fn fork_with_progress(
+        &self,
+        client: &GitHubClient,
+        progress: &(dyn Fn(MirrorProgress) + Send + Sync),
+    ) -> Result<ForkResult, FreshEyesError> {
+        let fetch_params = format!("/repos/{}/{}/forks", self.owner, self.repo);
+
         let value = json!({
             "default_branch_only": false
         });
-        let response = fetch_github_data(&fetch_params, RequestMethod::POST(value)).await;
-        return match response {
+        progress(MirrorProgress::Forking {
+            upstream: format!("{}/{}", self.owner, self.repo),
+        });
+        let response = client
+            .fetch_github_data(&fetch_params, RequestMethod::POST(value))
+            .await;
+        let fork_result = match response {
+            Ok(data) => ForkResult::from(serde_json::from_value::<Repository>(data)?),
+            Err(e) => return Err(FreshEyesError::ForkError(Box::new(e))),
+        };
+
+        self.wait_until_ready(client, &fork_result, progress)
+            .await?;
+        Ok(fork_result)
+    }
+
+    /// the user's existing fork, without creating one: the repository of the user named like
+    /// upstream, else the fork of the user among the forks of upstream, in case it was renamed.
+    /// `None` when the user hasn't forked the repository.
+    pub async fn find(&self, client: &GitHubClient) -> Result<Option<ForkResult>, FreshEyesError> {
+        let login = get_authenticated_user(client).await?.login;
+        let upstream = format!("{}/{}", self.owner, self.repo);
+        let fetch_params = format!("/repos/{}/{}", login, self.repo);
+        match client
+            .fetch_github_data(&fetch_params, RequestMethod::GET)
+            .await
+        {
             Ok(data) => {
-                let forked_repo = data["html_url"].as_str().unwrap_or_default().to_string();
-                let owner = data["owner"]["login"]
-                    .as_str()
-                    .unwrap_or_default()
-                    .to_string();
-                let fork_result = ForkResult {
-                    owner,
-                    repo: self.repo.to_string(),
-                    forked_repo,
-                };
-                Ok(fork_result)
+                let repository = serde_json::from_value::<Repository>(data)?;
+                let is_fork = repository
+                    .parent
+                    .as_ref()
+                    .is_some_and(|parent| parent.full_name.eq_ignore_ascii_case(&upstream));
+                if is_fork {
+                    return Ok(Some(repository.into()));
+                }
             }
-            Err(e) => Err(FreshEyesError::ForkError(format!(
-                "error forking the reposotory: {:?}",
-                e
-            ))),
-        };
+            Err(FreshEyesError::NotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+
+        let forks = client
+            .fetch_github_data_paginated(&format!("/repos/{}/forks", upstream))
+            .await?;
+        for fork in forks {
+            let fork = serde_json::from_value::<Repository>(fork)?;
+            if fork.owner.login.eq_ignore_ascii_case(&login) {
+                return Ok(Some(fork.into()));
+            }
+        }
+        Ok(None)
     }
+
+    /// GitHub answers the fork request right away and copies the repository in the background,
+    /// so poll the fork and its branches until they're there or `ready_timeout` runs out
+    async fn wait_until_ready(
+        &self,
+        client: &GitHubClient,
+        fork: &ForkResult,
+        progress: &(dyn Fn(MirrorProgress) + Send + Sync),
+    ) -> Result<(), FreshEyesError> {
+        let full_name = format!("{}/{}", fork.owner, fork.repo);
+        let started = Instant::now();
+        let mut attempt = 1;
+
+        loop {
+            match fork_has_branches(client, fork).await {
+                Ok(true) => break,
+                // 404 until the repository exists, no branches while they're being copied
+                Ok(false) | Err(FreshEyesError::NotFound(_)) => {}
+                // an empty upstream gives an empty fork, it would never get branches
+                Err(FreshEyesError::Conflict(e)) if is_empty_repository(&e) => {
+                    return Err(FreshEyesError::Conflict(e))
+                }
+                Err(FreshEyesError::Conflict(_)) => {}
+                Err(e) => return Err(FreshEyesError::ForkError(Box::new(e))),
+            }
+
+            let waited = started.elapsed();
+            if waited + self.poll_interval > self.ready_timeout {
+                return Err(FreshEyesError::ForkNotReady {
+                    fork: full_name,
+                    waited_secs: waited.as_secs(),
+                });
+            }
+            progress(MirrorProgress::WaitingForFork {
+                fork: full_name.clone(),
+                attempt,
+                waited_secs: waited.as_secs(),
+            });
+            tokio::time::sleep(self.poll_interval).await;
+            attempt += 1;
+        }
+
+        progress(MirrorProgress::ForkReady { fork: full_name });
+        Ok(())
+    }
+}
+
+/// whether GitHub refused a request because the repository has no commits
+fn is_empty_repository(error: &ErrorResponse) -> bool {
+    error.message.contains("Git Repository is empty")
+}
+
+/// whether the fork exists and its branches have been copied over
+async fn fork_has_branches(
+    client: &GitHubClient,
+    fork: &ForkResult,
+) -> Result<bool, FreshEyesError> {
+    client
+        .fetch_github_data(
+            &format!("/repos/{}/{}", fork.owner, fork.repo),
+            RequestMethod::GET,
+        )
+        .await?;
+    let branches = client
+        .fetch_github_data(
+            &format!("/repos/{}/{}/git/refs/heads", fork.owner, fork.repo),
+            RequestMethod::GET,
+        )
+        .await?;
+    Ok(branches.as_array().is_some_and(|refs| !refs.is_empty()))
 }
 
 impl<'a> Branch<'a> {
@@ -291,11 +495,11 @@ impl<'a> Branch<'a> {
     }
 
     /// create a new branch from an existing branch
-    pub async fn create(&self) -> Result<Value, FreshEyesError> {
-        let fetch_params = format!(
-            "https://api.github.com/repos/{}/{}/git/refs",
-            self.owner, self.repo
-        );
+    pub async fn create(
+        &self,
+        client: &GitHubClient,
+    ) -> Result<CreateOutcome<GitReference>, FreshEyesError> {
+        let fetch_params = format!("/repos/{}/{}/git/refs", self.owner, self.repo);
 
         let value = json!(
             {
@@ -303,117 +507,1883 @@ impl<'a> Branch<'a> {
                 "sha": self.sha
             }
         );
-        let response = fetch_github_data(&fetch_params, RequestMethod::POST(value)).await;
-        return match response {
-            Ok(data) => Ok::<Value, FreshEyesError>(data),
-            Err(e) => match e {
-                FreshEyesError::StatusCodeError(error_response) => {
-                    if error_response.status == StatusCode::UNPROCESSABLE_ENTITY.as_u16() {
-                        Ok(json!({
-                            "message": format!("Branch already exists!"),
-                            "status": 422
-                        }))
-                    } else {
-                        Err(FreshEyesError::StatusCodeError(error_response))
-                    }
-                }
-                _ => Err(FreshEyesError::Unknown(format!("unknown error: {:?}", e))),
-            },
-        };
+        let response = client
+            .fetch_github_data(&fetch_params, RequestMethod::POST(value))
+            .await;
+        match response {
+            Ok(data) => Ok(CreateOutcome::Created(serde_json::from_value(data)?)),
+            Err(FreshEyesError::Validation(error_response))
+                if error_response.is_already_exists() =>
+            {
+                Ok(CreateOutcome::AlreadyExists(error_response))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// the branch, `None` if it doesn't exist
+    pub async fn get(&self, client: &GitHubClient) -> Result<Option<GitReference>, FreshEyesError> {
+        let fetch_params = format!(
+            "/repos/{}/{}/git/ref/heads/{}",
+            self.owner, self.repo, self.branch_ref
+        );
+
+        match client
+            .fetch_github_data(&fetch_params, RequestMethod::GET)
+            .await
+        {
+            Ok(data) => Ok(Some(serde_json::from_value(data)?)),
+            Err(FreshEyesError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// point an existing branch at `sha`, without `force` GitHub only allows fast-forwards
+    pub async fn update(
+        &self,
+        client: &GitHubClient,
+        force: bool,
+    ) -> Result<GitReference, FreshEyesError> {
+        let fetch_params = format!(
+            "/repos/{}/{}/git/refs/heads/{}",
+            self.owner, self.repo, self.branch_ref
+        );
+
+        let value = json!(
+            {
+                "sha": self.sha,
+                "force": force
+            }
+        );
+        let data = client
+            .fetch_github_data(&fetch_params, RequestMethod::PATCH(value))
+            .await?;
+        Ok(serde_json::from_value(data)?)
+    }
+
+    /// delete the branch, deleting one that doesn't exist is not an error
+    pub async fn delete(&self, client: &GitHubClient) -> Result<(), FreshEyesError> {
+        let fetch_params = format!(
+            "/repos/{}/{}/git/refs/heads/{}",
+            self.owner, self.repo, self.branch_ref
+        );
+
+        match client
+            .fetch_github_data(&fetch_params, RequestMethod::DELETE)
+            .await
+        {
+            // 422 "Reference does not exist"
+            Ok(_) | Err(FreshEyesError::NotFound(_)) | Err(FreshEyesError::Validation(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
     }
 }
 
+/// the user the client's token belongs to
+pub async fn get_authenticated_user(client: &GitHubClient) -> Result<UserFields, FreshEyesError> {
+    let data = client
+        .fetch_github_data("/user", RequestMethod::GET)
+        .await?;
+    Ok(serde_json::from_value(data)?)
+}
+
+/// the review comments on the lines of a pull request
 pub async fn get_pull_request_reviews(
+    client: &GitHubClient,
     owner: &str,
     repo: &str,
     pull_number: u64,
 ) -> Result<Vec<ReviewComment>, FreshEyesError> {
-    let fetch_params = format!(
-        "https://api.github.com/repos/{}/{}/pulls/{}/comments",
-        owner, repo, pull_number
-    );
-    let response = fetch_github_data(&fetch_params, RequestMethod::GET).await?;
-    serde_json::from_value::<Vec<ReviewComment>>(response)
-        .map_err(|e| FreshEyesError::Unknown(format!("Deserialization error: {:?}", e)))
+    let fetch_params = format!("/repos/{}/{}/pulls/{}/comments", owner, repo, pull_number);
+    let response = client.fetch_github_data_paginated(&fetch_params).await?;
+    Ok(serde_json::from_value::<Vec<ReviewComment>>(Value::Array(
+        response,
+    ))?)
 }
 
-pub async fn fetch_github_data(url: &str, method: RequestMethod) -> Result<Value, FreshEyesError> {
-    let client = Client::new();
-    let mut headers = HeaderMap::new();
+/// the comments on the conversation of an issue or pull request
+pub async fn get_issue_comments(
+    client: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    issue_number: u64,
+) -> Result<Vec<IssueComment>, FreshEyesError> {
+    let fetch_params = format!("/repos/{}/{}/issues/{}/comments", owner, repo, issue_number);
+    let response = client.fetch_github_data_paginated(&fetch_params).await?;
+    Ok(serde_json::from_value(Value::Array(response))?)
+}
 
-    let token = match utils::get_or_prompt_token().await {
-        Ok(token) => token,
-        Err(_) => return Err(FreshEyesError::MissingTokenError),
-    };
-    headers.insert(header::USER_AGENT, "Fresh Eyes".parse().unwrap());
-    headers.insert(AUTHORIZATION, token.parse().unwrap());
-    headers.insert(
-        header::ACCEPT,
-        "application/vnd.github.v3+json".parse().unwrap(),
-    );
+/// the reviews submitted on a pull request, e.g. approvals
+pub async fn get_submitted_reviews(
+    client: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    pull_number: u64,
+) -> Result<Vec<Review>, FreshEyesError> {
+    let fetch_params = format!("/repos/{}/{}/pulls/{}/reviews", owner, repo, pull_number);
+    let response = client.fetch_github_data_paginated(&fetch_params).await?;
+    Ok(serde_json::from_value(Value::Array(response))?)
+}
 
-    let response = match method {
-        RequestMethod::GET => client.get(url).headers(headers).send().await?,
-        RequestMethod::POST(body) => client.post(url).headers(headers).json(&body).send().await?,
-    };
+/// the commits of a pull request, oldest first, at most 250
+pub async fn get_pull_request_commits(
+    client: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    pull_number: u64,
+) -> Result<Vec<PullRequestCommit>, FreshEyesError> {
+    let fetch_params = format!("/repos/{}/{}/pulls/{}/commits", owner, repo, pull_number);
+    let response = client.fetch_github_data_paginated(&fetch_params).await?;
+    Ok(serde_json::from_value(Value::Array(response))?)
+}
 
-    // check the status code
-    if !response.status().is_success() {
-        return Err(FreshEyesError::StatusCodeError(ErrorResponse {
-            message: format!("status code is not a OK response: {:?}", response.status())
-                .to_string(),
-            status: response.status().as_u16(),
-        }));
-    }
+/// the comments on a commit, on its lines or on the whole commit
+pub async fn get_commit_comments(
+    client: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    commit_sha: &str,
+) -> Result<Vec<CommitComment>, FreshEyesError> {
+    let fetch_params = format!("/repos/{}/{}/commits/{}/comments", owner, repo, commit_sha);
+    let response = client.fetch_github_data_paginated(&fetch_params).await?;
+    Ok(serde_json::from_value(Value::Array(response))?)
+}
 
-    Ok(response.json().await?)
+/// The pseudonym of the author in a blind mirror, see [`author_pseudonym`]
+fn blind_author(data: &PullRequestData) -> Result<String, FreshEyesError> {
+    let base_repo = data.base.repo.as_ref().ok_or_else(|| {
+        FreshEyesError::ValueUndefinedError(format!("the repository of {}", data.base.label))
+    })?;
+    Ok(author_pseudonym(
+        &blind::blind_secret()?,
+        &base_repo.owner.login,
+        &base_repo.name,
+        data.number,
+        &data.user.login,
+    ))
 }
 
-pub fn extract_pr_details(data: &Value) -> PullRequestDetails {
-    let base_sha = data["base"]["sha"].as_str().unwrap_or_default().to_string();
-    let head_sha = data["head"]["sha"].as_str().unwrap_or_default().to_string();
-    let base_ref = format!(
-        "{}-fresheyes-{}-{}",
-        data["base"]["user"]["login"]
-            .as_str()
-            .unwrap_or_default()
-            .to_string(),
-        data["base"]["ref"].as_str().unwrap_or_default().to_string(),
-        data["number"].as_u64().unwrap_or_default().to_string()
-    );
-    let head_ref = format!(
-        "{}-fresheyes-{}-{}",
-        data["head"]["user"]["login"]
-            .as_str()
-            .unwrap_or_default()
-            .to_string(),
-        data["head"]["ref"].as_str().unwrap_or_default().to_string(),
-        data["number"].as_u64().unwrap_or_default().to_string()
-    );
-    let title = data["title"].as_str().unwrap_or_default().to_string();
-    let body = data["body"].as_str().unwrap_or_default().to_string();
+/// The branches, title and body of the mirror of a pull request, worded as `style` says.
+/// With `blind` the author is left out, see [`author_pseudonym`].
+pub fn extract_pr_details(
+    data: &PullRequestData,
+    blind: bool,
+    style: MirrorStyle,
+) -> Result<PullRequestDetails, FreshEyesError> {
+    let base_sha = required(&data.base.sha, "base.sha")?.to_string();
+    let head_sha = required(&data.head.sha, "head.sha")?.to_string();
+    let pseudonym = if blind {
+        Some(blind_author(data)?)
+    } else {
+        None
+    };
+    let hide = |text: &str| match &pseudonym {
+        Some(pseudonym) => hide_author(text, &data.user.login, pseudonym),
+        None => text.to_string(),
+    };
+    let head_user = data.head.owner_login()?;
+    let head_user = match &pseudonym {
+        // the head branch usually lives in the author's fork
+        Some(pseudonym) if head_user.eq_ignore_ascii_case(&data.user.login) => pseudonym,
+        _ => head_user,
+    };
+
+    let template = BranchTemplate::from_env()?;
+    let base_ref = template.branch_name(
+        data.base.owner_login()?,
+        required(&data.base.ref_name, "base.ref")?,
+        data.number,
+    )?;
+    let head_ref = template.branch_name(
+        head_user,
+        required(&data.head.ref_name, "head.ref")?,
+        data.number,
+    )?;
+    let title = hide(&data.title);
+    // nobody mentioned, referenced or linked upstream should be notified of the mirror
+    let body = modify_pull_request_body(&hide(data.body.as_deref().unwrap_or_default()));
 
-    PullRequestDetails {
+    let details = PullRequestDetails {
         base_sha,
         head_sha,
         base_ref,
         head_ref,
         title,
         body,
+    };
+    match style {
+        MirrorStyle::Plain => Ok(details),
+        MirrorStyle::Introduced => introduce(data, details, pseudonym.as_deref()),
     }
 }
 
+/// The mirror the server opens: the title and body introduce the upstream pull request, since
+/// its users don't pick the mirror themselves
+fn introduce(
+    data: &PullRequestData,
+    details: PullRequestDetails,
+    pseudonym: Option<&str>,
+) -> Result<PullRequestDetails, FreshEyesError> {
+    let base_repo = data.base.repo.as_ref().ok_or_else(|| {
+        FreshEyesError::ValueUndefinedError(format!("the repository of {}", data.base.label))
+    })?;
+    // the title of the details already leaves a blind author out
+    let original_pr_name = &details.title;
+    let title = format!("[FreshEyes] {}", original_pr_name);
+    let original_author = pseudonym.unwrap_or(&data.user.login);
+    let issue_number = data.number;
+    let org = &base_repo.owner.login;
+    let repo = &base_repo.name;
+
+    let body = format!("The author **{}** wrote the following PR called **{}**, issue number **{}** in **{}/{}** cloned by FreshEyes below:\n\n{}",
+                       original_author,
+                       original_pr_name,
+                       issue_number,
+                       org,
+                       repo,
+                       match pseudonym {
+                           Some(pseudonym) => hide_author(data.body.as_deref().unwrap_or_default(), &data.user.login, pseudonym),
+                           None => data.body.clone().unwrap_or_default(),
+                       }
+    );
+
+    // nobody mentioned, referenced or linked upstream should be notified of the mirror
+    let modified_body = modify_pull_request_body(&body);
+
+    Ok(PullRequestDetails {
+        title,
+        body: modified_body,
+        ..details
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::client::next_page_url;
+    use reqwest::header::{self, HeaderMap};
+
+    /// a client that sends its requests to the given mock server
+    pub(crate) fn test_client(server: &mockito::Server) -> GitHubClient {
+        GitHubClient::with_config(GitHubClientConfig {
+            base_url: server.url(),
+            ..GitHubClientConfig::default()
+        })
+        .unwrap()
+        .with_token("test-token".to_string())
+    }
+
+    /// the secret the pseudonyms of blind mirrors are derived from in the tests,
+    /// every test sets the same one so they can run in parallel
+    const TEST_BLIND_SECRET: &str = "test secret";
+
+    pub(crate) fn set_blind_secret() {
+        std::env::set_var("FRESHEYES_BLIND_SECRET", TEST_BLIND_SECRET);
+    }
+
+    /// pull request `owner/repo#number` as GitHub sends it, opened by `author` from their
+    /// branch `head_ref` onto master. Tests change the fields they care about.
+    pub(crate) fn pull_request_json(
+        owner: &str,
+        repo: &str,
+        number: u64,
+        author: &str,
+        head_ref: &str,
+    ) -> Value {
+        json!({
+            "number": number,
+            "title": "Rounding",
+            "body": null,
+            "state": "open",
+            "html_url": format!("https://github.com/{}/{}/pull/{}", owner, repo, number),
+            "user": {"login": author},
+            "base": {
+                "label": format!("{}:master", owner),
+                "ref": "master",
+                "sha": "base",
+                "user": {"login": owner},
+                "repo": {
+                    "name": repo,
+                    "full_name": format!("{}/{}", owner, repo),
+                    "owner": {"login": owner},
+                    "html_url": format!("https://github.com/{}/{}", owner, repo)
+                }
+            },
+            "head": {
+                "label": format!("{}:{}", author, head_ref),
+                "ref": head_ref,
+                "sha": "head",
+                "user": {"login": author},
+                "repo": null
+            },
+            "created_at": "2011-02-03T00:00:00Z",
+            "updated_at": "2011-02-03T00:00:00Z",
+            "closed_at": null,
+            "merged_at": null
+        })
+    }
 
     #[tokio::test]
     async fn test_fetch_github_data() {
-        let url = "https://api.github.com/users/extheoisah/repos";
-        let res = fetch_github_data(url, RequestMethod::GET).await;
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/users/extheoisah/repos")
+            .match_header("authorization", "Bearer test-token")
+            .match_header("user-agent", "Fresh Eyes")
+            .match_header("x-github-api-version", "2022-11-28")
+            .with_body(r#"[{"name": "fresheyes"}]"#)
+            .create_async()
+            .await;
+
+        let client = test_client(&server);
+        let res = client
+            .fetch_github_data("/users/extheoisah/repos", RequestMethod::GET)
+            .await;
+
+        mock.assert_async().await;
         assert!(res.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_fetch_github_data_without_body() {
+        let mut server = mockito::Server::new_async().await;
+        let delete = server
+            .mock("DELETE", "/repos/fresheyes/bitcoin/git/refs/heads/rounding")
+            .with_status(204)
+            .create_async()
+            .await;
+        let put = server
+            .mock("PUT", "/user/starred/bitcoin/bitcoin")
+            .with_status(200)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let deleted = client
+            .fetch_github_data(
+                "/repos/fresheyes/bitcoin/git/refs/heads/rounding",
+                RequestMethod::DELETE,
+            )
+            .await;
+        let starred = client
+            .fetch_github_data(
+                "/user/starred/bitcoin/bitcoin",
+                RequestMethod::PUT(json!({})),
+            )
+            .await;
+
+        delete.assert_async().await;
+        put.assert_async().await;
+        assert_eq!(deleted.unwrap(), Value::Null);
+        assert_eq!(starred.unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_next_page_url() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::LINK,
+            r#"<https://api.github.com/repositories/1/pulls/2/comments?per_page=100&page=2>; rel="next", <https://api.github.com/repositories/1/pulls/2/comments?per_page=100&page=4>; rel="last""#
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            next_page_url(&headers).as_deref(),
+            Some("https://api.github.com/repositories/1/pulls/2/comments?per_page=100&page=2")
+        );
+
+        headers.insert(
+            header::LINK,
+            r#"<https://api.github.com/repositories/1/pulls/2/comments?per_page=100&page=1>; rel="prev""#
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(next_page_url(&headers), None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_github_data_paginated() {
+        let mut server = mockito::Server::new_async().await;
+        let first_page = server
+            .mock("GET", "/comments")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "per_page".into(),
+                "100".into(),
+            ))
+            .with_header(
+                "link",
+                &format!(
+                    r#"<{}/comments?per_page=100&page=2>; rel="next""#,
+                    server.url()
+                ),
+            )
+            .with_body(r#"[{"id": 1}, {"id": 2}]"#)
+            .create_async()
+            .await;
+        let second_page = server
+            .mock("GET", "/comments")
+            .match_query(mockito::Matcher::UrlEncoded("page".into(), "2".into()))
+            .with_body(r#"[{"id": 3}]"#)
+            .create_async()
+            .await;
+
+        let res = test_client(&server)
+            .fetch_github_data_paginated("/comments")
+            .await
+            .unwrap();
+
+        first_page.assert_async().await;
+        second_page.assert_async().await;
+        let ids: Vec<u64> = res.iter().map(|c| c["id"].as_u64().unwrap()).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_github_data_retries_secondary_rate_limit() {
+        let mut server = mockito::Server::new_async().await;
+        let rate_limited = server
+            .mock("GET", "/user")
+            .with_status(429)
+            .with_header("retry-after", "0")
+            .expect(1)
+            .create_async()
+            .await;
+        let ok = server
+            .mock("GET", "/user")
+            .with_body(r#"{"login": "fresheyes"}"#)
+            .create_async()
+            .await;
+
+        let res = test_client(&server)
+            .fetch_github_data("/user", RequestMethod::GET)
+            .await
+            .unwrap();
+
+        rate_limited.assert_async().await;
+        ok.assert_async().await;
+        assert_eq!(res["login"], "fresheyes");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_github_data_retries_server_errors_only_when_idempotent() {
+        let mut server = mockito::Server::new_async().await;
+        let failed_get = server
+            .mock("GET", "/repos/bitcoin/bitcoin")
+            .with_status(502)
+            .expect(1)
+            .create_async()
+            .await;
+        let ok = server
+            .mock("GET", "/repos/bitcoin/bitcoin")
+            .with_body(r#"{"name": "bitcoin"}"#)
+            .create_async()
+            .await;
+        // the pull request may have been opened before the server failed, don't open it twice
+        let failed_post = server
+            .mock("POST", "/repos/fresheyes/bitcoin/pulls")
+            .with_status(502)
+            .expect(1)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let res = client
+            .fetch_github_data("/repos/bitcoin/bitcoin", RequestMethod::GET)
+            .await
+            .unwrap();
+        let post = client
+            .fetch_github_data(
+                "/repos/fresheyes/bitcoin/pulls",
+                RequestMethod::POST(json!({"title": "title"})),
+            )
+            .await;
+
+        failed_get.assert_async().await;
+        ok.assert_async().await;
+        failed_post.assert_async().await;
+        assert_eq!(res["name"], "bitcoin");
+        assert!(post.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_github_data_retries_secondary_rate_limit_message() {
+        let mut server = mockito::Server::new_async().await;
+        // no retry-after, only the message tells it apart from a missing permission
+        let rate_limited = server
+            .mock("GET", "/user")
+            .with_status(403)
+            .with_body(r#"{"message": "You have exceeded a secondary rate limit. Please wait a few minutes before you try again."}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let ok = server
+            .mock("GET", "/user")
+            .with_body(r#"{"login": "fresheyes"}"#)
+            .create_async()
+            .await;
+
+        let res = test_client(&server)
+            .fetch_github_data("/user", RequestMethod::GET)
+            .await
+            .unwrap();
+
+        rate_limited.assert_async().await;
+        ok.assert_async().await;
+        assert_eq!(res["login"], "fresheyes");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_graphql_retries_server_errors() {
+        let mut server = mockito::Server::new_async().await;
+        // a POST, but the query only reads
+        let failed = server
+            .mock("POST", "/graphql")
+            .with_status(502)
+            .expect(1)
+            .create_async()
+            .await;
+        let ok = server
+            .mock("POST", "/graphql")
+            .with_body(r#"{"data": {"viewer": {"login": "fresheyes"}}}"#)
+            .create_async()
+            .await;
+
+        let res = test_client(&server)
+            .fetch_graphql("query { viewer { login } }", json!({}))
+            .await
+            .unwrap();
+
+        failed.assert_async().await;
+        ok.assert_async().await;
+        assert_eq!(res["viewer"]["login"], "fresheyes");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_github_data_reports_exhausted_rate_limit() {
+        let mut server = mockito::Server::new_async().await;
+        // the rate limit resets in an hour, far longer than we are willing to wait
+        let reset_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 3600;
+        let mock = server
+            .mock("GET", "/user")
+            .with_status(403)
+            .with_header("x-ratelimit-limit", "5000")
+            .with_header("x-ratelimit-remaining", "0")
+            .with_header("x-ratelimit-reset", &reset_at.to_string())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let res = test_client(&server)
+            .fetch_github_data("/user", RequestMethod::GET)
+            .await;
+
+        mock.assert_async().await;
+        match res {
+            Err(FreshEyesError::RateLimited {
+                reset_at: reported,
+                remaining,
+            }) => {
+                assert_eq!(reported, reset_at);
+                assert_eq!(remaining, Some(0));
+            }
+            other => panic!("expected a rate limit error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_github_data_keeps_github_error() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/repos/fresheyes/bitcoin/git/refs")
+            .with_status(422)
+            .with_body(
+                r#"{
+                    "message": "Validation Failed",
+                    "errors": [{"resource": "Reference", "code": "custom", "message": "Reference already exists"}],
+                    "documentation_url": "https://docs.github.com/rest/git/refs#create-a-reference"
+                }"#,
+            )
+            .create_async()
+            .await;
+
+        let res = test_client(&server)
+            .fetch_github_data(
+                "/repos/fresheyes/bitcoin/git/refs",
+                RequestMethod::POST(json!({"ref": "refs/heads/master", "sha": "abc"})),
+            )
+            .await;
+
+        mock.assert_async().await;
+        match res {
+            Err(FreshEyesError::Validation(error_response)) => {
+                assert_eq!(error_response.status, 422);
+                assert_eq!(error_response.message, "Validation Failed");
+                assert_eq!(
+                    error_response.errors[0].message.as_deref(),
+                    Some("Reference already exists")
+                );
+                assert_eq!(
+                    error_response.to_string(),
+                    "Validation Failed (HTTP 422): Reference already exists, see https://docs.github.com/rest/git/refs#create-a-reference"
+                );
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_github_data_hides_non_json_error() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/8149")
+            .with_status(404)
+            .with_body("<html><body>internal proxy 10.0.0.7 says no</body></html>")
+            .create_async()
+            .await;
+
+        let res = test_client(&server)
+            .fetch_github_data("/repos/bitcoin/bitcoin/pulls/8149", RequestMethod::GET)
+            .await;
+
+        mock.assert_async().await;
+        match res {
+            Err(FreshEyesError::NotFound(error_response)) => {
+                assert_eq!(error_response.status, 404);
+                assert_eq!(error_response.message, "Not Found");
+            }
+            other => panic!("expected a not found error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_distinguishes_already_exists_from_failure() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/repos/fresheyes/bitcoin/git/refs")
+            .with_status(422)
+            .with_body(r#"{"message": "Reference already exists"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/repos/fresheyes/bitcoin/pulls")
+            .with_status(422)
+            .with_body(
+                r#"{
+                    "message": "Validation Failed",
+                    "errors": [{"resource": "PullRequest", "code": "custom", "message": "No commits between master and rounding"}]
+                }"#,
+            )
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let branch = Branch::new("fresheyes", "bitcoin", "master", "abc")
+            .create(&client)
+            .await;
+        assert!(matches!(branch, Ok(CreateOutcome::AlreadyExists(_))));
+
+        let pull_request = PullRequest::new(
+            "fresheyes",
+            "bitcoin",
+            Some("title"),
+            None,
+            "master",
+            "rounding",
+        )
+        .create(&client)
+        .await;
+        assert!(matches!(pull_request, Err(FreshEyesError::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_find_open_returns_existing_mirror() {
+        let mut server = mockito::Server::new_async().await;
+        let mirror = pull_request_json("fresheyes", "bitcoin", 3, "fresheyes", "rounding");
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin/pulls")
+            .match_query(mockito::Matcher::Exact(
+                "state=open&head=fresheyes:rounding&base=master&per_page=100".to_string(),
+            ))
+            .with_body(json!([mirror]).to_string())
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let existing = PullRequest::new(
+            "fresheyes",
+            "bitcoin",
+            Some("title"),
+            None,
+            "master",
+            "rounding",
+        )
+        .find_open(&client)
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(existing.number, 3);
+        assert_eq!(
+            existing.html_url,
+            "https://github.com/fresheyes/bitcoin/pull/3"
+        );
+    }
+
+    /// mock `POST /repos/bitcoin/bitcoin/forks` answering with the fork `fresheyes/bitcoin`
+    pub(crate) async fn mock_fork(server: &mut mockito::Server) -> mockito::Mock {
+        server
+            .mock("POST", "/repos/bitcoin/bitcoin/forks")
+            .with_status(202)
+            .with_body(
+                r#"{
+                    "name": "bitcoin",
+                    "full_name": "fresheyes/bitcoin",
+                    "owner": {"login": "fresheyes"},
+                    "html_url": "https://github.com/fresheyes/bitcoin",
+                    "fork": true,
+                    "default_branch": "master"
+                }"#,
+            )
+            .create_async()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_fork_waits_until_ready() {
+        let mut server = mockito::Server::new_async().await;
+        mock_fork(&mut server).await;
+        // first check: the fork doesn't exist yet
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin")
+            .with_status(404)
+            .with_body(r#"{"message": "Not Found"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        // second check: the fork exists but its branches are still being copied
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin/git/refs/heads")
+            .with_body("[]")
+            .expect(1)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin")
+            .with_body(r#"{"name": "bitcoin"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin/git/refs/heads")
+            .with_body(r#"[{"ref": "refs/heads/master"}]"#)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let steps = std::sync::Mutex::new(Vec::new());
+        let fork = ForkRequest {
+            poll_interval: Duration::from_millis(1),
+            ..ForkRequest::new("bitcoin", "bitcoin")
+        }
+        .fork_with_progress(&client, &|step| steps.lock().unwrap().push(step))
+        .await
+        .unwrap();
+
+        assert_eq!(fork.owner, "fresheyes");
+        assert_eq!(fork.repo, "bitcoin");
+        let steps = steps.into_inner().unwrap();
+        assert!(matches!(steps[0], MirrorProgress::Forking { .. }));
+        assert!(matches!(
+            steps[1],
+            MirrorProgress::WaitingForFork { attempt: 1, .. }
+        ));
+        assert!(matches!(
+            steps[2],
+            MirrorProgress::WaitingForFork { attempt: 2, .. }
+        ));
+        assert!(matches!(steps[3], MirrorProgress::ForkReady { .. }));
+        assert_eq!(steps.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_fork_of_empty_repository_fails() {
+        let mut server = mockito::Server::new_async().await;
+        mock_fork(&mut server).await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin")
+            .with_body(r#"{"name": "bitcoin"}"#)
+            .create_async()
+            .await;
+        let refs = server
+            .mock("GET", "/repos/fresheyes/bitcoin/git/refs/heads")
+            .with_status(409)
+            .with_body(r#"{"message": "Git Repository is empty."}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let fork = ForkRequest {
+            poll_interval: Duration::from_millis(1),
+            ..ForkRequest::new("bitcoin", "bitcoin")
+        }
+        .fork(&client)
+        .await;
+
+        assert!(matches!(fork, Err(FreshEyesError::Conflict(_))));
+        refs.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_fork_gives_up_after_timeout() {
+        let mut server = mockito::Server::new_async().await;
+        mock_fork(&mut server).await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin")
+            .with_status(404)
+            .with_body(r#"{"message": "Not Found"}"#)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let fork = ForkRequest {
+            ready_timeout: Duration::from_millis(20),
+            poll_interval: Duration::from_millis(5),
+            ..ForkRequest::new("bitcoin", "bitcoin")
+        }
+        .fork(&client)
+        .await;
+
+        assert!(matches!(fork, Err(FreshEyesError::ForkNotReady { .. })));
+    }
+
+    #[test]
+    fn test_branch_template_names() {
+        let default = BranchTemplate::new(DEFAULT_BRANCH_TEMPLATE, "").unwrap();
+        let staging = BranchTemplate::new(STAGING_BRANCH_TEMPLATE, "staging").unwrap();
+        let custom = BranchTemplate::new("fresheyes/{env}/{user}/{number}-{ref}", "dev").unwrap();
+        let long_ref = "a".repeat(300);
+        let cases = [
+            (
+                &default,
+                "gavinandresen",
+                "rounding",
+                "gavinandresen-fresheyes-rounding-79",
+            ),
+            (
+                &staging,
+                "bitcoin",
+                "master",
+                "bitcoin-fresheyes-staging-master-79",
+            ),
+            (
+                &custom,
+                "bitcoin",
+                "master",
+                "fresheyes/dev/bitcoin/79-master",
+            ),
+            // slashes, .., forbidden characters, leading dots and .lock are sanitized
+            (
+                &default,
+                "fanquake",
+                "2024/06-depends",
+                "fanquake-fresheyes-2024-06-depends-79",
+            ),
+            (&default, "sipa", "a..b", "sipa-fresheyes-a.b-79"),
+            (
+                &default,
+                "sipa",
+                "fix: [wip] ~1^2?*",
+                "sipa-fresheyes-fix---wip]--1-2---79",
+            ),
+            (
+                &default,
+                "sipa",
+                ".hidden/x.lock",
+                "sipa-fresheyes-hidden-x-79",
+            ),
+            (
+                &custom,
+                "sipa",
+                "feature/x.lock",
+                "fresheyes/dev/sipa/79-feature-x",
+            ),
+            // characters reserved in urls would break the queries and paths the name goes in
+            (
+                &default,
+                "sipa",
+                "c++/fix#1&50%",
+                "sipa-fresheyes-c---fix-1-50--79",
+            ),
+            // nothing is left of the ref, it still has a value
+            (&default, "sipa", "...", "sipa-fresheyes-_-79"),
+        ];
+        for (template, user, ref_name, expected) in cases {
+            let name = template.branch_name(user, ref_name, 79).unwrap();
+            assert_eq!(name, expected, "{}", ref_name);
+            assert_eq!(check_ref_format(&name), Ok(()));
+        }
+
+        // long branch names are shortened to fit
+        let name = default.branch_name("sipa", &long_ref, 79).unwrap();
+        assert_eq!(name.len(), MAX_BRANCH_NAME_LEN);
+        assert!(name.starts_with("sipa-fresheyes-aaa") && name.ends_with("a-79"));
+    }
+
+    #[test]
+    fn test_branch_template_parse() {
+        let staging = BranchTemplate::new(STAGING_BRANCH_TEMPLATE, "staging").unwrap();
+        let custom = BranchTemplate::new("fresheyes/{user}/{number}-{ref}", "").unwrap();
+        let cases = [
+            (
+                &staging,
+                "bitcoin-fresheyes-staging-master-79",
+                Some(("bitcoin", "master", 79)),
+            ),
+            (
+                &custom,
+                "fresheyes/fanquake/30000-2024-06-depends",
+                Some(("fanquake", "2024-06-depends", 30000)),
+            ),
+            // branches of the default templates are still recognized
+            (
+                &custom,
+                "gavinandresen-fresheyes-rounding-79",
+                Some(("gavinandresen", "rounding", 79)),
+            ),
+            (&custom, "fresheyes/fanquake/depends", None),
+            (&custom, "master", None),
+        ];
+        for (template, name, expected) in cases {
+            let parsed = template
+                .parse(name)
+                .map(|branch| (branch.user, branch.branch, branch.pull_number));
+            assert_eq!(parsed, expected, "{}", name);
+        }
+
+        // names round-trip
+        let name = custom.branch_name("sipa", "a-1-b", 8149).unwrap();
+        let branch = custom.parse(&name).unwrap();
+        assert_eq!(
+            (branch.user, branch.branch, branch.pull_number),
+            ("sipa", "a-1-b", 8149)
+        );
+        // even when nothing is left of the ref
+        let default = BranchTemplate::new(DEFAULT_BRANCH_TEMPLATE, "").unwrap();
+        let name = default.branch_name("sipa", "..", 5).unwrap();
+        let branch = default.parse(&name).unwrap();
+        assert_eq!(
+            (branch.user, branch.branch, branch.pull_number),
+            ("sipa", "_", 5)
+        );
+    }
+
+    #[test]
+    fn test_invalid_branch_templates() {
+        let cases = [
+            ("{user}-fresheyes-{ref}", "{number} is missing"),
+            // the base and head branches of a mirror would get the same name
+            ("fresheyes-{ref}-{number}", "{user} is missing"),
+            ("{user}-fresheyes-{number}", "{ref} is missing"),
+            ("{user}-{branch}-{number}", "unknown placeholder {branch}"),
+            ("{user}-{ref}-{number", "unbalanced { or }"),
+            (
+                "fresh eyes/{user}/{ref}-{number}",
+                "it contains a space, a control character or one of ~^:?*[\\",
+            ),
+            (
+                "fresheyes//{user}/{ref}-{number}",
+                "it has an empty path component",
+            ),
+            (
+                "{user}-{ref}-{number}.lock",
+                "a path component starts with . or ends with .lock",
+            ),
+            ("-{user}-{ref}-{number}", "it starts with -"),
+        ];
+        for (template, expected) in cases {
+            match BranchTemplate::new(template, "") {
+                Err(FreshEyesError::InvalidBranchTemplate { reason, .. }) => {
+                    assert_eq!(reason, expected, "{}", template)
+                }
+                other => panic!("{} was accepted: {:?}", template, other),
+            }
+        }
+
+        let cases = [
+            ("", Err("it is empty")),
+            ("@", Err("it is @")),
+            ("a..b", Err("it contains ..")),
+            ("a@{1}", Err("it contains @{")),
+            ("a/", Err("it has an empty path component")),
+            ("a.", Err("it ends with .")),
+            (
+                "a/.b",
+                Err("a path component starts with . or ends with .lock"),
+            ),
+            (
+                "a/b.lock/c",
+                Err("a path component starts with . or ends with .lock"),
+            ),
+            ("sipa-fresheyes-a.b-79", Ok(())),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(check_ref_format(name), expected, "{}", name);
+        }
+    }
+
+    /// mock the lookup of the fork of bitcoin/bitcoin the user `fresheyes` renamed to
+    /// `bitcoin-fork`, it is only found among the forks of bitcoin/bitcoin
+    pub(crate) async fn mock_existing_fork(server: &mut mockito::Server) {
+        server
+            .mock("GET", "/user")
+            .with_body(r#"{"login": "fresheyes"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin")
+            .with_status(404)
+            .with_body(r#"{"message": "Not Found"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/forks")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"[
+                    {"name": "bitcoin", "full_name": "sipa/bitcoin", "owner": {"login": "sipa"}, "html_url": "https://github.com/sipa/bitcoin"},
+                    {"name": "bitcoin-fork", "full_name": "fresheyes/bitcoin-fork", "owner": {"login": "fresheyes"}, "html_url": "https://github.com/fresheyes/bitcoin-fork"}
+                ]"#,
+            )
+            .create_async()
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_find_fork_by_name() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/user")
+            .with_body(r#"{"login": "fresheyes"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin")
+            .with_body(
+                r#"{
+                    "name": "bitcoin",
+                    "full_name": "fresheyes/bitcoin",
+                    "owner": {"login": "fresheyes"},
+                    "html_url": "https://github.com/fresheyes/bitcoin",
+                    "fork": true,
+                    "parent": {"name": "bitcoin", "full_name": "bitcoin/bitcoin", "owner": {"login": "bitcoin"}, "html_url": "https://github.com/bitcoin/bitcoin"}
+                }"#,
+            )
+            .create_async()
+            .await;
+        // no need to go through the forks of upstream
+        let forks = server
+            .mock("GET", "/repos/bitcoin/bitcoin/forks")
+            .match_query(mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let fork = ForkRequest::new("bitcoin", "bitcoin")
+            .find(&client)
+            .await
+            .unwrap()
+            .unwrap();
+
+        forks.assert_async().await;
+        assert_eq!(fork.owner, "fresheyes");
+        assert_eq!(fork.repo, "bitcoin");
+    }
+
+    /// a review comment by `login` on `line` of `path`, `None` once the line is outdated
+    pub(crate) fn review_comment(
+        id: u64,
+        login: &str,
+        path: &str,
+        line: Option<u64>,
+        created_at: &str,
+    ) -> serde_json::Value {
+        json!({
+            "id": id,
+            "body": "nit",
+            "commit_id": "8a9cad44a57f1e0057c127ced5078d7e722b9cc8",
+            "path": path,
+            "line": line,
+            "start_line": null,
+            "original_line": 10,
+            "position": null,
+            "original_position": 4,
+            "side": "RIGHT",
+            "start_side": null,
+            "url": format!("https://api.github.com/repos/bitcoin/bitcoin/pulls/comments/{}", id),
+            "html_url": format!("https://github.com/bitcoin/bitcoin/pull/79#discussion_r{}", id),
+            "subject_type": "line",
+            "created_at": created_at,
+            "updated_at": created_at,
+            "user": {"login": login, "type": "User"}
+        })
+    }
+
+    #[test]
+    fn test_review_threads() {
+        let comment = |id: u64, path: &str, created_at: &str, in_reply_to_id: Option<u64>| {
+            let mut comment = review_comment(id, "sipa", path, Some(5), created_at);
+            comment["in_reply_to_id"] = json!(in_reply_to_id);
+            comment
+        };
+        let mut left = comment(23, "src/a.cpp", "2011-02-08T10:01:00Z", None);
+        left["side"] = json!("LEFT");
+        let comments: Vec<ReviewComment> = serde_json::from_value(json!([
+            comment(22, "src/a.cpp", "2011-02-08T10:10:00Z", Some(21)),
+            comment(20, "src/a.cpp", "2011-02-08T10:00:00Z", None),
+            comment(21, "src/a.cpp", "2011-02-08T10:05:00Z", Some(20)),
+            left,
+            comment(26, "src/a.cpp", "2011-02-08T11:00:00Z", Some(24)),
+            comment(24, "src/a.cpp", "2011-02-08T09:00:00Z", None),
+            // replies to a deleted comment
+            comment(25, "src/b.cpp", "2011-02-08T08:00:00Z", Some(19)),
+        ]))
+        .unwrap();
+
+        let threads = ReviewThread::from_comments(comments);
+
+        let summary: Vec<(&str, &str, Vec<u64>)> = threads
+            .iter()
+            .map(|thread| {
+                (
+                    thread.path.as_str(),
+                    thread.side.as_str(),
+                    thread.comments().map(|comment| comment.id).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("src/a.cpp", "LEFT", vec![23]),
+                ("src/a.cpp", "RIGHT", vec![24, 26]),
+                ("src/a.cpp", "RIGHT", vec![20, 21, 22]),
+                ("src/b.cpp", "RIGHT", vec![25]),
+            ]
+        );
+        assert_eq!(threads[1].line, Some(5));
+    }
+
+    #[tokio::test]
+    async fn test_resolved_threads() {
+        let mut server = mockito::Server::new_async().await;
+        let thread = |is_resolved: bool, is_outdated: bool, ids: &[u64]| {
+            json!({
+                "isResolved": is_resolved,
+                "isOutdated": is_outdated,
+                "comments": {"nodes": ids.iter().map(|id| json!({"databaseId": id})).collect::<Vec<_>>()}
+            })
+        };
+        let page = |threads: Vec<serde_json::Value>, end_cursor: Option<&str>| {
+            json!({"data": {"repository": {"pullRequest": {"reviewThreads": {
+                "pageInfo": {"hasNextPage": end_cursor.is_some(), "endCursor": end_cursor},
+                "nodes": threads
+            }}}}})
+            .to_string()
+        };
+        let first_page = server
+            .mock("POST", "/graphql")
+            .match_body(mockito::Matcher::PartialJson(
+                json!({"variables": {"owner": "bitcoin", "number": 79, "after": null}}),
+            ))
+            .with_body(page(
+                vec![thread(true, false, &[30, 31, 32])],
+                Some("Y3Vyc29y"),
+            ))
+            .expect(1)
+            .create_async()
+            .await;
+        let second_page = server
+            .mock("POST", "/graphql")
+            .match_body(mockito::Matcher::PartialJson(
+                json!({"variables": {"after": "Y3Vyc29y"}}),
+            ))
+            .with_body(page(
+                vec![thread(false, false, &[33]), thread(true, true, &[34])],
+                None,
+            ))
+            .expect(1)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let states = get_review_thread_states(&client, "bitcoin", "bitcoin", 79)
+            .await
+            .unwrap();
+
+        first_page.assert_async().await;
+        second_page.assert_async().await;
+        assert_eq!(states.len(), 3);
+        assert!(states[0].is_resolved);
+        assert_eq!(states[0].comment_ids, [30, 31, 32]);
+        assert!(!states[1].is_resolved);
+        assert!(states[2].is_outdated);
+
+        let mut first_reply =
+            review_comment(31, "laanwj", "src/a.cpp", Some(5), "2011-02-08T10:05:00Z");
+        first_reply["in_reply_to_id"] = json!(30);
+        let mut second_reply =
+            review_comment(32, "sipa", "src/a.cpp", Some(5), "2011-02-08T10:06:00Z");
+        second_reply["in_reply_to_id"] = json!(30);
+        let review_comments: Vec<ReviewComment> = serde_json::from_value(json!([
+            review_comment(30, "sipa", "src/a.cpp", Some(5), "2011-02-08T10:00:00Z"),
+            first_reply,
+            second_reply,
+            review_comment(33, "sipa", "src/b.cpp", Some(7), "2011-02-08T11:00:00Z"),
+            review_comment(34, "sipa", "src/c.cpp", None, "2011-02-08T09:00:00Z"),
+        ]))
+        .unwrap();
+        let mut discussion = Discussion::new(review_comments, Vec::new(), Vec::new(), Vec::new());
+        discussion.mark_resolved(&states);
+        let threads = discussion.review_threads();
+        assert_eq!(
+            threads
+                .iter()
+                .map(|thread| thread.resolved)
+                .collect::<Vec<_>>(),
+            [true, false, true]
+        );
+
+        let shown = plan_reveal(&discussion, "gavinandresen", ResolvedThreads::Show);
+        assert_eq!(shown.len(), 3);
+        assert_eq!(
+            shown[0],
+            RevealComment::Issue {
+                body: "There was 1 comment left by 1 reviewer for this pull request".to_string()
+            }
+        );
+
+        let collapsed = plan_reveal(&discussion, "gavinandresen", ResolvedThreads::Collapse);
+        match &collapsed[1] {
+            RevealComment::Review { body, path, .. } => {
+                assert_eq!(path, "src/a.cpp");
+                assert_eq!(
+                    body,
+                    "2 authors commented here with:\n\n\
+                     - comment link `https://github.com/bitcoin/bitcoin/pull/79#discussion_r30` at 2011/02/08, 10:00:00 UTC, resolved with 2 replies."
+                );
+            }
+            other => panic!("expected a review comment, got {:?}", other),
+        }
+
+        let hidden = plan_reveal(&discussion, "gavinandresen", ResolvedThreads::Hide);
+        assert_eq!(hidden.len(), 2);
+        assert_eq!(
+            hidden[0],
+            RevealComment::Issue {
+                body: "There were no comments on the conversation of this pull request".to_string()
+            }
+        );
+        assert!(matches!(
+            &hidden[1],
+            RevealComment::Review { path, .. } if path == "src/b.cpp"
+        ));
+
+        // GraphQL answers errors with 200 OK
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/graphql")
+            .with_body(
+                r#"{"data": {"repository": {"pullRequest": null}}, "errors": [{"type": "NOT_FOUND", "message": "Could not resolve to a PullRequest with the number of 80."}]}"#,
+            )
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let res = get_review_thread_states(&client, "bitcoin", "bitcoin", 80).await;
+
+        match res {
+            Err(FreshEyesError::GraphQL(message)) => {
+                assert_eq!(
+                    message,
+                    "Could not resolve to a PullRequest with the number of 80."
+                )
+            }
+            other => panic!("expected a GraphQL error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_pull_request_locator() {
+        let cases = [
+            (
+                "https://github.com/bitcoin/bitcoin/pull/8149",
+                ("github.com", "bitcoin", "bitcoin", 8149),
+            ),
+            (
+                "https://github.com/bitcoin/bitcoin/pull/8149/files",
+                ("github.com", "bitcoin", "bitcoin", 8149),
+            ),
+            (
+                "https://github.com/bitcoin/bitcoin/pull/8149/commits/abc123",
+                ("github.com", "bitcoin", "bitcoin", 8149),
+            ),
+            (
+                "https://github.com/bitcoin/bitcoin/pull/8149/files?w=1#diff-123",
+                ("github.com", "bitcoin", "bitcoin", 8149),
+            ),
+            (
+                "github.com/bitcoin-core/gui/pull/12/",
+                ("github.com", "bitcoin-core", "gui", 12),
+            ),
+            (
+                "https://GitHub.Example.com/chaincode/fresh.eyes/pull/3",
+                ("github.example.com", "chaincode", "fresh.eyes", 3),
+            ),
+            (
+                "bitcoin/bitcoin#8149",
+                ("github.com", "bitcoin", "bitcoin", 8149),
+            ),
+            (
+                " bitcoin/bitcoin/pull/8149 ",
+                ("github.com", "bitcoin", "bitcoin", 8149),
+            ),
+        ];
+        for (input, (host, owner, repo, pull_number)) in cases {
+            assert_eq!(
+                input.parse::<PullRequestLocator>().unwrap(),
+                PullRequestLocator {
+                    host: host.to_string(),
+                    owner: owner.to_string(),
+                    repo: repo.to_string(),
+                    pull_number,
+                },
+                "{}",
+                input
+            );
+        }
+
+        // the shorthand takes the given host, links keep their own
+        let shorthand = PullRequestLocator::parse("chaincode/fresheyes#3", "github.example.com");
+        assert_eq!(shorthand.unwrap().host, "github.example.com");
+        let link = PullRequestLocator::parse(
+            "https://github.com/bitcoin/bitcoin/pull/1",
+            "github.example.com",
+        );
+        assert_eq!(link.unwrap().host, "github.com");
+
+        for input in [
+            "",
+            "bitcoin/bitcoin",
+            "bitcoin/bitcoin#",
+            "bitcoin/bitcoin#0",
+            "bitcoin#8149",
+            "https://github.com/bitcoin/bitcoin/issues/8149",
+            "https://github.com/bitcoin/bitcoin/pull/abc",
+            "https://github.com/bitcoin/bitcoin",
+        ] {
+            assert!(
+                matches!(
+                    input.parse::<PullRequestLocator>(),
+                    Err(FreshEyesError::InvalidPullRequest(_))
+                ),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mirror_pull_requests_reports_failures_per_pull_request() {
+        let mut server = mockito::Server::new_async().await;
+        let fork = mock_fork(&mut server).await.expect(1);
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin")
+            .with_body(r#"{"name": "bitcoin"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin/git/refs/heads")
+            .with_body(r#"[{"ref": "refs/heads/master"}]"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/79")
+            .with_body(
+                pull_request_json("bitcoin", "bitcoin", 79, "gavinandresen", "rounding")
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/git/ref/pull/79/head")
+            .with_body(
+                r#"{"ref": "refs/pull/79/head", "url": "", "object": {"sha": "head", "type": "commit", "url": ""}}"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/80")
+            .with_status(404)
+            .with_body(r#"{"message": "Not Found"}"#)
+            .create_async()
+            .await;
+        server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/repos/fresheyes/bitcoin/git/commits/".to_string()),
+            )
+            .with_body(r#"{"sha": "head"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/repos/fresheyes/bitcoin/git/refs")
+            .with_status(201)
+            .with_body(
+                r#"{"ref": "refs/heads/x", "url": "", "object": {"sha": "head", "type": "commit", "url": ""}}"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/repos/fresheyes/bitcoin/pulls")
+            .with_status(201)
+            .with_body(
+                pull_request_json("fresheyes", "bitcoin", 1, "fresheyes", "rounding").to_string(),
+            )
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let batch = mirror_pull_requests(
+            &client,
+            &ForkRequest::new("bitcoin", "bitcoin"),
+            &[80, 79],
+            2,
+            false,
+            MirrorStyle::Plain,
+            &|_, _| {},
+        )
+        .await
+        .unwrap();
+
+        fork.assert_async().await;
+        assert_eq!(batch.fork.owner, "fresheyes");
+        assert_eq!(batch.mirrors.len(), 2);
+        assert_eq!(batch.mirrors[0].pull_number, 80);
+        assert!(batch.mirrors[0].pr_url.is_none());
+        assert!(batch.mirrors[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("pull request not found"));
+        assert_eq!(batch.mirrors[1].pull_number, 79);
+        assert_eq!(
+            batch.mirrors[1].pr_url.as_deref(),
+            Some("https://github.com/fresheyes/bitcoin/pull/1")
+        );
+        assert!(batch.mirrors[1].created);
+        assert!(batch.mirrors[1].error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_find_pull_requests_by_label() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/issues")
+            .match_query(mockito::Matcher::Exact(
+                "state=open&labels=Review%20club&per_page=100".to_string(),
+            ))
+            .with_body(
+                r#"[
+                    {"number": 8149, "pull_request": {"url": ""}},
+                    {"number": 8150},
+                    {"number": 8151, "pull_request": {"url": ""}}
+                ]"#,
+            )
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let pull_numbers =
+            find_pull_requests_by_label(&client, "bitcoin", "bitcoin", "Review club")
+                .await
+                .unwrap();
+
+        assert_eq!(pull_numbers, [8149, 8151]);
+    }
+
+    #[test]
+    fn test_neutralize_mentions() {
+        let cases = [
+            ("Thanks @sipa!", "Thanks `@sipa`!"),
+            ("cc @bitcoin/maintainers", "cc `@bitcoin/maintainers`"),
+            ("@laanwj: ACK", "`@laanwj`: ACK"),
+            ("> @jnewbery wrote", "> `@jnewbery` wrote"),
+            ("> > nested @jnewbery", "> > nested `@jnewbery`"),
+            ("- [ ] @fanquake to review", "- [ ] `@fanquake` to review"),
+            ("1. ask @MarcoFalke", "1. ask `@MarcoFalke`"),
+            (
+                "- item\n\n    continued by @achow101",
+                "- item\n\n    continued by `@achow101`",
+            ),
+            ("| @theuni | ACK |", "| `@theuni` | ACK |"),
+            (
+                "[@sipa](https://github.com/sipa)",
+                "[`@sipa`](https://github.com/sipa)",
+            ),
+            ("@a-b-c and @x-", "`@a-b-c` and `@x`-"),
+            // code is left alone
+            (
+                "run `git log --author=@sipa`",
+                "run `git log --author=@sipa`",
+            ),
+            ("``a ` @sipa``", "``a ` @sipa``"),
+            ("```\n@Override\n```\n@sipa", "```\n@Override\n```\n`@sipa`"),
+            ("> ~~~\n> @Override\n> ~~~", "> ~~~\n> @Override\n> ~~~"),
+            ("text\n\n    @decorator\n", "text\n\n    @decorator\n"),
+            // not mentions
+            ("mail satoshi@gmx.com", "mail satoshi@gmx.com"),
+            (
+                "see https://github.com/@sipa",
+                "see https://github.com/@sipa",
+            ),
+            ("an @ sign and @-", "an @ sign and @-"),
+            // a stray backtick can't pair with the code span around the mention
+            ("an unclosed ` and @sipa", "an unclosed ` and ``@sipa``"),
+            ("a `` and @sipa", "a `` and ```@sipa```"),
+            ("@sipa and ` after", "``@sipa`` and ` after"),
+        ];
+        for (body, expected) in cases {
+            let neutralized = neutralize_mentions(body);
+            assert_eq!(neutralized, expected, "{}", body);
+            assert_eq!(
+                live_mentions(&neutralized),
+                Vec::<String>::new(),
+                "{}",
+                body
+            );
+        }
+    }
+
+    /// the mentions GitHub would notify when rendering the markdown
+    fn live_mentions(markdown: &str) -> Vec<String> {
+        use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+        let mut mentions = Vec::new();
+        let mut in_code_block = false;
+        for event in Parser::new(markdown) {
+            let text = match event {
+                Event::Start(Tag::CodeBlock(_)) => {
+                    in_code_block = true;
+                    continue;
+                }
+                Event::End(TagEnd::CodeBlock) => {
+                    in_code_block = false;
+                    continue;
+                }
+                Event::Text(text) if !in_code_block => text,
+                _ => continue,
+            };
+            let mut previous = None;
+            for (i, c) in text.char_indices() {
+                let after_word = previous.is_some_and(|c: char| {
+                    c.is_alphanumeric() || matches!(c, '_' | '-' | '/' | '.' | '@')
+                });
+                let user: String = text[i + 1..]
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+                    .collect();
+                if c == '@' && !after_word && !user.trim_matches('-').is_empty() {
+                    mentions.push(format!("@{}", user));
+                }
+                previous = Some(c);
+            }
+        }
+        mentions
+    }
+
+    #[test]
+    fn test_neutralize_references() {
+        let forms: [(&str, &[(&str, &str)]); 7] = [
+            (
+                "issue and pull request numbers",
+                &[
+                    ("fixes #123", "fixes `#123`"),
+                    ("(#8149)", "(`#8149`)"),
+                    ("#1, #2.", "`#1`, `#2`."),
+                    ("see issue#12", "see issue#12"),
+                    ("#12abc and #", "#12abc and #"),
+                    ("&#39;", "&#39;"),
+                ],
+            ),
+            (
+                "GH- numbers",
+                &[
+                    ("GH-26", "`GH-26`"),
+                    ("closes gh-26.", "closes `gh-26`."),
+                    ("GH-", "GH-"),
+                    ("NGH-26 and GH-26a", "NGH-26 and GH-26a"),
+                ],
+            ),
+            (
+                "cross-repository numbers",
+                &[
+                    ("bitcoin/bitcoin#8149", "`bitcoin/bitcoin#8149`"),
+                    ("after bitcoin-core/gui#1, ", "after `bitcoin-core/gui#1`, "),
+                    ("a/b.c_d#2", "`a/b.c_d#2`"),
+                    ("src/net.cpp#L12", "src/net.cpp#L12"),
+                    ("x/bitcoin/bitcoin#8149", "x/bitcoin/bitcoin#8149"),
+                ],
+            ),
+            (
+                "commit SHAs",
+                &[
+                    ("in a1b2c3d", "in `a1b2c3d`"),
+                    (
+                        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4 fixed it",
+                        "`e3b0c44298fc1c149afbf4c8996fb92427ae41e4` fixed it",
+                    ),
+                    (
+                        "deadbeefdeadbeefdeadbeefdeadbeefdeadbeef",
+                        "`deadbeefdeadbeefdeadbeefdeadbeefdeadbeef`",
+                    ),
+                    ("a1b2c3d.", "`a1b2c3d`."),
+                    // words, numbers, file names, hex over 40 characters and uuids
+                    ("defaced and 1234567", "defaced and 1234567"),
+                    ("a1b2c3", "a1b2c3"),
+                    ("cafe123.com", "cafe123.com"),
+                    (
+                        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4a",
+                        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4a",
+                    ),
+                    (
+                        "550e8400-e29b-41d4-a716-446655440000",
+                        "550e8400-e29b-41d4-a716-446655440000",
+                    ),
+                    ("0x1234abcd", "0x1234abcd"),
+                ],
+            ),
+            (
+                "owner commit SHAs",
+                &[
+                    ("sipa@a1b2c3d", "`sipa@a1b2c3d`"),
+                    ("mail sipa@a1b2c3d.com", "mail sipa@a1b2c3d.com"),
+                ],
+            ),
+            (
+                "cross-repository commit SHAs",
+                &[
+                    ("bitcoin/bitcoin@a1b2c3d", "`bitcoin/bitcoin@a1b2c3d`"),
+                    ("bitcoin/bitcoin@main", "bitcoin/bitcoin@main"),
+                ],
+            ),
+            (
+                "references in code and links",
+                &[
+                    ("`#123` and `a1b2c3d`", "`#123` and `a1b2c3d`"),
+                    ("```\nfixes #123\n```", "```\nfixes #123\n```"),
+                    (
+                        "https://github.com/bitcoin/bitcoin/pull/8149#issuecomment-1",
+                        "https://github.com/bitcoin/bitcoin/pull/8149#issuecomment-1",
+                    ),
+                    (
+                        "https://github.com/bitcoin/bitcoin/commit/a1b2c3d",
+                        "https://github.com/bitcoin/bitcoin/commit/a1b2c3d",
+                    ),
+                    ("> fixes #123", "> fixes `#123`"),
+                ],
+            ),
+        ];
+        for (form, cases) in forms {
+            for (body, expected) in cases {
+                assert_eq!(neutralize_references(body), *expected, "{}: {}", form, body);
+            }
+        }
+
+        // mentions are only neutralized together with references
+        assert_eq!(neutralize_references("@sipa #1"), "@sipa `#1`");
+        assert_eq!(neutralize_mentions("@sipa #1"), "`@sipa` #1");
+        assert_eq!(
+            neutralize_autolinks("@sipa fixed #1 in a1b2c3d"),
+            "`@sipa` fixed `#1` in `a1b2c3d`"
+        );
+    }
+
+    #[test]
+    fn test_modify_pull_request_body() {
+        let cases = [
+            // code is left alone
+            (
+                "```\nsee https://github.com/bitcoin/bitcoin/pull/1 and #1\n```\n#2",
+                "```\nsee https://github.com/bitcoin/bitcoin/pull/1 and #1\n```\n`#2`",
+            ),
+            (
+                "`https://github.com/bitcoin/bitcoin` and `#1`",
+                "`https://github.com/bitcoin/bitcoin` and `#1`",
+            ),
+            (
+                "CODEBLOCK1 and IMGPLACEHOLDER0",
+                "CODEBLOCK1 and IMGPLACEHOLDER0",
+            ),
+            // images and html
+            (
+                "![screenshot](https://github.com/user-attachments/assets/1)",
+                "![screenshot](https://github.com/user-attachments/assets/1)",
+            ),
+            (
+                "<img width=\"200\" src=\"https://github.com/a.png\"> by @sipa",
+                "<img width=\"200\" src=\"https://github.com/a.png\"> by `@sipa`",
+            ),
+            (
+                "<details>\n<summary>Logs</summary>\n<!-- paste logs -->\n</details>\n",
+                "<details>\n<summary>Logs</summary>\n\n</details>\n",
+            ),
+            // text in html is neutralized with <code>, github renders no markdown there
+            (
+                "<p>@sipa fixes #123</p>",
+                "<p><code>@sipa</code> fixes <code>#123</code></p>",
+            ),
+            (
+                "<details><summary>cc @sipa</summary>\n\nsee #1\n</details>",
+                "<details><summary>cc <code>@sipa</code></summary>\n\nsee `#1`\n</details>",
+            ),
+            (
+                "<div>\n<code>@Override</code> <pre>\n#1\n</pre> by @sipa\n</div>",
+                "<div>\n<code>@Override</code> <pre>\n#1\n</pre> by <code>@sipa</code>\n</div>",
+            ),
+            (
+                "<table><tr><td title=\"#1\">&#39;a1b2c3d</td></tr></table>",
+                "<table><tr><td title=\"#1\">&#39;<code>a1b2c3d</code></td></tr></table>",
+            ),
+            // pull request template comments
+            (
+                "<!--\nPlease describe the change\n-->\nFixes #1",
+                "Fixes `#1`",
+            ),
+            ("ACK <!-- nit --> thanks", "ACK  thanks"),
+            ("<!-- x --> @sipa", " <code>@sipa</code>"),
+            // links to GitHub
+            (
+                "see [the issue](https://github.com/bitcoin/bitcoin/issues/1)",
+                "see the issue: (`https://github.com/bitcoin/bitcoin/issues/1`)",
+            ),
+            (
+                "see https://github.com/bitcoin/bitcoin/pull/1.",
+                "see `https://github.com/bitcoin/bitcoin/pull/1`.",
+            ),
+            (
+                "(https://github.com/bitcoin/bitcoin/pull/1)",
+                "(`https://github.com/bitcoin/bitcoin/pull/1`)",
+            ),
+            (
+                "<https://github.com/bitcoin/bitcoin/pull/1>",
+                "`https://github.com/bitcoin/bitcoin/pull/1`",
+            ),
+            (
+                "[docs](https://bitcoincore.org) https://bitcoincore.org",
+                "[docs](https://bitcoincore.org) https://bitcoincore.org",
+            ),
+            // images in links to GitHub
+            (
+                "[![build](a.png)](https://github.com/bitcoin/bitcoin/pull/2)",
+                "![build](a.png): (`https://github.com/bitcoin/bitcoin/pull/2`)",
+            ),
+            (
+                "[see ![build](a.png) here](https://github.com/bitcoin/bitcoin/pull/2) #3",
+                "see ![build](a.png) here: (`https://github.com/bitcoin/bitcoin/pull/2`) `#3`",
+            ),
+            (
+                "[![build](a.png)](https://bitcoincore.org) #3",
+                "[![build](a.png)](https://bitcoincore.org) `#3`",
+            ),
+        ];
+        for (body, expected) in cases {
+            assert_eq!(modify_pull_request_body(body), expected, "{}", body);
+        }
+
+        // everything else round-trips byte for byte
+        let body = "Title\r\n=====\r\n\r\n## Motivation  \n\n*Some* __emphasis__, ~~strike~~ and \\*escapes\\*.\n\n\
+                    | a | b |\n|:--|--:|\n| 1 | 2 |\n\n- [x] done\n   * nested\n\n> quote\n> more\n\n\
+                    [ref]: https://bitcoincore.org\n\n1) one\n\n---\n\n    indented code @sipa\n";
+        assert_eq!(modify_pull_request_body(body), body);
+    }
+
+    #[test]
+    fn test_api_base_url() {
+        let api_base_url = |host: &str| api_base_url(host).unwrap();
+        assert_eq!(api_base_url("github.com"), "https://api.github.com");
+        assert_eq!(
+            api_base_url("https://github.com/"),
+            "https://api.github.com"
+        );
+        assert_eq!(
+            api_base_url("GitHub.Example.com"),
+            "https://github.example.com/api/v3"
+        );
+        assert_eq!(
+            api_base_url("github.example.com:8443"),
+            "https://github.example.com:8443/api/v3"
+        );
+
+        let graphql_url = |host: &str| {
+            GitHubClient::with_config(GitHubClientConfig::for_host(host).unwrap())
+                .unwrap()
+                .graphql_url()
+        };
+        assert_eq!(graphql_url("github.com"), "https://api.github.com/graphql");
+        assert_eq!(
+            graphql_url("github.example.com"),
+            "https://github.example.com/api/graphql"
+        );
+    }
+
+    #[test]
+    fn test_invalid_hosts() {
+        // the host names the file its token is saved in
+        for host in [
+            "..",
+            "../../.ssh/id_rsa",
+            "github.example.com/../x",
+            "github.example.com\\..",
+            "https://github.example.com/bitcoin/bitcoin",
+            "github..example.com",
+            "github example.com",
+        ] {
+            assert!(
+                matches!(normalize_host(host), Err(FreshEyesError::InvalidHost(_))),
+                "{}",
+                host
+            );
+        }
+        assert_eq!(normalize_host(" ").unwrap(), "github.com");
+    }
+
+    #[test]
+    fn test_with_host() {
+        let client = GitHubClient::new().unwrap();
+        let enterprise = client
+            .with_host("github.example.com", "enterprise token".to_string())
+            .unwrap();
+        assert_eq!(enterprise.base_url(), "https://github.example.com/api/v3");
+        assert!(client.with_host("../x", String::new()).is_err());
+    }
+
+    #[test]
+    fn test_token_env_var() {
+        assert_eq!(utils::token_env_var("github.com"), "GITHUB_TOKEN");
+        assert_eq!(
+            utils::token_env_var("github.example.com"),
+            "GITHUB_TOKEN_GITHUB_EXAMPLE_COM"
+        );
+        assert_eq!(
+            utils::token_env_var("git-hub.corp:8443"),
+            "GITHUB_TOKEN_GIT_HUB_CORP_8443"
+        );
+    }
+
     #[test]
     fn test_extract_base_head_sha() {
         let data = r#"
@@ -421,21 +2391,117 @@ mod tests {
             "url": "https://api.github.com/repos/bitcoin/bitcoin/pulls/79",
             "id": 279147,
             "node_id": "MDExOlB1bGxSZXF1ZXN0Mjc5MTQ3",
+            "number": 79,
+            "title": "Rounding",
+            "body": null,
+            "state": "closed",
+            "html_url": "https://github.com/bitcoin/bitcoin/pull/79",
+            "user": { "login": "gavinandresen" },
+            "created_at": "2011-02-08T19:37:11Z",
+            "updated_at": "2011-02-09T17:25:40Z",
+            "closed_at": "2011-02-09T17:25:40Z",
+            "merged_at": "2011-02-09T17:25:40Z",
             "head": {
                 "label": "gavinandresen:rounding",
                 "ref": "rounding",
                 "sha": "8a9cad44a57f1e0057c127ced5078d7e722b9cc8",
+                "user": { "login": "gavinandresen" },
+                "repo": null
             },
             "base": {
                 "label": "bitcoin:master",
                 "ref": "master",
                 "sha": "ccd7fe8de52bbc9210b444838eefb7ddbc880457",
-            },
+                "user": { "login": "bitcoin" },
+                "repo": null
+            }
         }"#;
-        let res = extract_pr_details(&serde_json::from_str(data).unwrap());
+        let res = extract_pr_details(
+            &serde_json::from_str(data).unwrap(),
+            false,
+            MirrorStyle::Plain,
+        )
+        .unwrap();
         assert_eq!(res.base_sha, "ccd7fe8de52bbc9210b444838eefb7ddbc880457");
         assert_eq!(res.head_sha, "8a9cad44a57f1e0057c127ced5078d7e722b9cc8");
-        assert_eq!(res.base_ref, "master");
-        assert_eq!(res.head_ref, "rounding");
+        assert_eq!(res.base_ref, "bitcoin-fresheyes-master-79");
+        assert_eq!(res.head_ref, "gavinandresen-fresheyes-rounding-79");
+    }
+
+    #[test]
+    fn test_extract_pr_details_blind() {
+        let mut data = pull_request_json("bitcoin", "bitcoin", 79, "gavinandresen", "rounding");
+        data["title"] = json!("Rounding fix by gavinandresen");
+        data["body"] =
+            json!("@gavinandresen here, cc @sipa. Thanks Gavinandresen! gavinandresen-bot stays");
+        let data: PullRequestData = serde_json::from_value(data).unwrap();
+        set_blind_secret();
+        let pseudonym =
+            author_pseudonym(TEST_BLIND_SECRET, "bitcoin", "bitcoin", 79, "gavinandresen");
+
+        let res = extract_pr_details(&data, true, MirrorStyle::Plain).unwrap();
+        assert_eq!(res.title, format!("Rounding fix by {}", pseudonym));
+        assert_eq!(
+            res.body,
+            format!(
+                "{0} here, cc `@sipa`. Thanks {0}! gavinandresen-bot stays",
+                pseudonym
+            )
+        );
+        assert_eq!(res.base_ref, "bitcoin-fresheyes-master-79");
+        assert_eq!(res.head_ref, format!("{}-fresheyes-rounding-79", pseudonym));
+
+        let res = extract_pr_details(&data, false, MirrorStyle::Plain).unwrap();
+        assert_eq!(res.title, "Rounding fix by gavinandresen");
+        assert_eq!(res.head_ref, "gavinandresen-fresheyes-rounding-79");
+    }
+
+    #[test]
+    fn test_author_pseudonym() {
+        let pseudonym = author_pseudonym("secret", "bitcoin", "bitcoin", 79, "gavinandresen");
+        // stable, whatever the case of the names
+        assert_eq!(pseudonym, "author-ea1ece");
+        assert_eq!(
+            author_pseudonym("secret", "Bitcoin", "Bitcoin", 79, "GavinAndresen"),
+            pseudonym
+        );
+        // and different on every pull request
+        assert_ne!(
+            author_pseudonym("secret", "bitcoin", "bitcoin", 80, "gavinandresen"),
+            pseudonym
+        );
+        // and for every secret, the pseudonym can't be computed without it
+        assert_ne!(
+            author_pseudonym("other secret", "bitcoin", "bitcoin", 79, "gavinandresen"),
+            pseudonym
+        );
+
+        let cases = [
+            ("@sipa wrote this", "author-1 wrote this"),
+            ("thanks Sipa.", "thanks author-1."),
+            ("(sipa)", "(author-1)"),
+            ("sipa-bot and sipas", "sipa-bot and sipas"),
+            ("github.com/sipa/repo", "github.com/author-1/repo"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(hide_author(text, "sipa", "author-1"), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_extract_pr_details_without_head_owner() {
+        // the account that owns the head branch has been deleted
+        let mut data = pull_request_json("bitcoin", "bitcoin", 79, "gavinandresen", "rounding");
+        data["head"]["user"] = Value::Null;
+        let res = extract_pr_details(
+            &serde_json::from_value(data).unwrap(),
+            false,
+            MirrorStyle::Plain,
+        );
+        assert!(matches!(res, Err(FreshEyesError::ValueUndefinedError(_))));
+
+        // a payload without a head cannot be read at all
+        let res = serde_json::from_value::<PullRequestData>(json!({ "number": 79 }));
+        assert!(res.is_err());
     }
 }
@@ -0,0 +1,194 @@
+use serde::Serialize;
+
+use crate::{
+    cleanup::get_upstream, BranchTemplate, ForkRequest, FreshEyesError, GitHubClient,
+    PullRequestData, UpstreamState,
+};
+
+/// A mirror pull request in the user's fork and the upstream pull request it was made from
+#[derive(Debug, Serialize)]
+pub struct MirrorStatus {
+    /// url of the mirror pull request in the fork
+    pub mirror_pr_url: String,
+    pub upstream_owner: String,
+    pub upstream_repo: String,
+    pub upstream_pull_number: u64,
+    /// `None` when fetching the upstream pull request failed, see `error`
+    pub upstream_state: Option<UpstreamState>,
+    /// `false` when upstream moved on since the mirror was made, see `refresh_mirror`,
+    /// `None` when the upstream pull request is gone or couldn't be fetched
+    pub head_up_to_date: Option<bool>,
+    /// number of review comments on the upstream pull request
+    pub upstream_review_comments: u64,
+    /// why fetching the upstream pull request failed, the other mirrors are still listed
+    pub error: Option<String>,
+}
+
+/// List the open mirror pull requests in the user's fork of `owner/repo`,
+/// ordered by upstream pull request number, none when the user hasn't forked the repository
+pub async fn list_mirrors(
+    client: &GitHubClient,
+    owner: &str,
+    repo: &str,
+) -> Result<Vec<MirrorStatus>, FreshEyesError> {
+    let template = BranchTemplate::from_env()?;
+    let Some(fork) = ForkRequest::new(owner, repo).find(client).await? else {
+        return Ok(Vec::new());
+    };
+    let pulls = client
+        .fetch_github_data_paginated(&format!(
+            "/repos/{}/{}/pulls?state=open",
+            fork.owner, fork.repo
+        ))
+        .await?;
+
+    let mut mirrors = Vec::new();
+    for pull in pulls {
+        let mirror: PullRequestData = serde_json::from_value(pull)?;
+        let Some(branch) = template.parse(&mirror.head.ref_name) else {
+            continue;
+        };
+        let upstream_pull_number = branch.pull_number;
+
+        let mut status = MirrorStatus {
+            mirror_pr_url: mirror.html_url,
+            upstream_owner: owner.to_string(),
+            upstream_repo: repo.to_string(),
+            upstream_pull_number,
+            upstream_state: None,
+            head_up_to_date: None,
+            upstream_review_comments: 0,
+            error: None,
+        };
+        match get_upstream(client, owner, repo, upstream_pull_number).await {
+            Ok(upstream) => {
+                status.upstream_state = Some(UpstreamState::of(upstream.as_ref()));
+                if let Some(upstream) = upstream {
+                    status.head_up_to_date = Some(mirror.head.sha == upstream.head.sha);
+                    status.upstream_review_comments = upstream.review_comments.unwrap_or_default();
+                }
+            }
+            Err(e) => status.error = Some(e.to_string()),
+        }
+        mirrors.push(status);
+    }
+    mirrors.sort_by_key(|mirror| mirror.upstream_pull_number);
+
+    Ok(mirrors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{mock_existing_fork, pull_request_json, test_client};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_list_mirrors() {
+        let mut server = mockito::Server::new_async().await;
+        mock_existing_fork(&mut server).await;
+        let pull_request = |number: u64, head_ref: &str, head_sha: &str| {
+            let mut pull_request =
+                pull_request_json("fresheyes", "bitcoin-fork", number, "fresheyes", head_ref);
+            pull_request["head"]["sha"] = json!(head_sha);
+            pull_request["review_comments"] = json!(12);
+            pull_request
+        };
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin-fork/pulls")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                json!([
+                    pull_request(2, "sipa-fresheyes-segwit-80", "segwit"),
+                    pull_request(1, "gavinandresen-fresheyes-rounding-79", "stale"),
+                    pull_request(3, "my-own-branch", "mine"),
+                    pull_request(4, "laanwj-fresheyes-gone-81", "gone"),
+                    pull_request(5, "jonasnick-fresheyes-hidden-82", "hidden")
+                ])
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/79")
+            .with_body(pull_request(79, "rounding", "rebased").to_string())
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/80")
+            .with_body(pull_request(80, "segwit", "segwit").to_string())
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/81")
+            .with_status(404)
+            .with_body(r#"{"message": "Not Found"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/82")
+            .with_status(403)
+            .with_body(r#"{"message": "Resource not accessible by integration"}"#)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let mirrors = list_mirrors(&client, "bitcoin", "bitcoin").await.unwrap();
+
+        assert_eq!(mirrors.len(), 4);
+        assert_eq!(mirrors[0].upstream_pull_number, 79);
+        assert_eq!(
+            mirrors[0].mirror_pr_url,
+            "https://github.com/fresheyes/bitcoin-fork/pull/1"
+        );
+        assert_eq!(mirrors[0].head_up_to_date, Some(false));
+        assert_eq!(mirrors[0].upstream_review_comments, 12);
+        assert_eq!(mirrors[1].upstream_pull_number, 80);
+        assert_eq!(mirrors[1].head_up_to_date, Some(true));
+        assert_eq!(mirrors[1].upstream_state, Some(UpstreamState::Open));
+        // the upstream pull request of the mirror is gone, the others are still listed
+        assert_eq!(mirrors[2].upstream_pull_number, 81);
+        assert_eq!(mirrors[2].upstream_state, Some(UpstreamState::Gone));
+        assert_eq!(mirrors[2].head_up_to_date, None);
+        assert_eq!(mirrors[3].upstream_pull_number, 82);
+        assert_eq!(mirrors[3].upstream_state, None);
+        assert!(mirrors[3]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("Resource not accessible"));
+    }
+
+    #[tokio::test]
+    async fn test_list_mirrors_without_fork() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/user")
+            .with_body(r#"{"login": "fresheyes"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin")
+            .with_status(404)
+            .with_body(r#"{"message": "Not Found"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/forks")
+            .match_query(mockito::Matcher::Any)
+            .with_body("[]")
+            .create_async()
+            .await;
+        let fork = server
+            .mock("POST", "/repos/bitcoin/bitcoin/forks")
+            .expect(0)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let mirrors = list_mirrors(&client, "bitcoin", "bitcoin").await.unwrap();
+
+        fork.assert_async().await;
+        assert!(mirrors.is_empty());
+    }
+}
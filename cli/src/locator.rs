@@ -0,0 +1,91 @@
+use serde::Serialize;
+use std::{fmt, str::FromStr};
+
+use crate::{normalize_host, FreshEyesError, GITHUB_HOST};
+
+/// Where to find a pull request, parsed from what reviewers paste: a link like
+/// `https://github.com/bitcoin/bitcoin/pull/8149/files` or the shorthand `bitcoin/bitcoin#8149`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PullRequestLocator {
+    /// `github.com` or a GitHub Enterprise Server host
+    pub host: String,
+    pub owner: String,
+    pub repo: String,
+    pub pull_number: u32,
+}
+
+impl PullRequestLocator {
+    /// Parse a pull request url or `owner/repo#number`.
+    /// Urls carry their host, `default_host` is used for the shorthand.
+    pub fn parse(input: &str, default_host: &str) -> Result<Self, FreshEyesError> {
+        let input = input.trim();
+        let invalid = || FreshEyesError::InvalidPullRequest(input.to_string());
+
+        // owner/repo#number
+        if let Some((repository, pull_number)) = input.split_once('#') {
+            if let Some((owner, repo)) = repository.split_once('/') {
+                if !repo.contains('/') {
+                    return Self::new(default_host, owner, repo, pull_number).ok_or_else(invalid);
+                }
+            }
+        }
+
+        // [https://]host/owner/repo/pull/number[/files|/commits|...][?query][#fragment]
+        let url = input
+            .strip_prefix("https://")
+            .or_else(|| input.strip_prefix("http://"))
+            .unwrap_or(input);
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let (host, segments) = match segments.as_slice() {
+            // bitcoin/bitcoin/pull/8149 without a host
+            [_, _, "pull" | "pulls", ..] => (default_host, segments.as_slice()),
+            [host, rest @ ..] => (*host, rest),
+            [] => return Err(invalid()),
+        };
+        match segments {
+            [owner, repo, "pull" | "pulls", pull_number, ..] => {
+                Self::new(host, owner, repo, pull_number).ok_or_else(invalid)
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    fn new(host: &str, owner: &str, repo: &str, pull_number: &str) -> Option<Self> {
+        let valid_name = |name: &str| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        };
+        if !valid_name(owner) || !valid_name(repo) {
+            return None;
+        }
+        let pull_number = pull_number.parse().ok().filter(|n| *n > 0)?;
+        Some(Self {
+            host: normalize_host(host).ok()?,
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            pull_number,
+        })
+    }
+}
+
+impl FromStr for PullRequestLocator {
+    type Err = FreshEyesError;
+
+    /// parse with github.com as the host of the shorthand
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Self::parse(input, GITHUB_HOST)
+    }
+}
+
+impl fmt::Display for PullRequestLocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "https://{}/{}/{}/pull/{}",
+            self.host, self.owner, self.repo, self.pull_number
+        )
+    }
+}
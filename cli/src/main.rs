@@ -1,93 +1,262 @@
 mod cli;
 
-use cli::CliArgs;
-use fresh_eyes::{extract_pr_details, get_pull_request_reviews, Branch, ForkRequest, PullRequest};
-use std::{process::exit};
-
+use cli::{CliArgs, Command};
+use fresh_eyes::{
+    cleanup_mirrors, find_pull_requests_by_label, get_or_prompt_token, list_mirrors,
+    mirror_pull_request, mirror_pull_requests, refresh_mirror, reveal, BatchResult, BranchRefresh,
+    CleanupResult, ForkRequest, FreshEyesError, GitHubClient, GitHubClientConfig, MirrorProgress,
+    MirrorStatus, MirrorStyle, RefUpdate, RevealResult,
+};
+use std::process::exit;
 
 async fn run(args: CliArgs) -> Result<(), Box<dyn std::error::Error>> {
     let CliArgs {
+        command,
         owner,
         repo,
-        pr_number,
+        host,
     } = args;
 
-    // create a fork of the base repository
-    let fork = ForkRequest::new(&owner, &repo);
-    let fork_result = fork.fork().await?;
-
-    // fetch the desired pull request
-    let pull_request = PullRequest::from_pull_number(&fork.owner, &fork.repo, pr_number);
-    let pull_request_response = pull_request.get().await?;
-    let pull_request_details = extract_pr_details(&pull_request_response);
-
-    // create a branch for the base repository
-    Branch::new(
-        &fork_result.owner,
-        &fork_result.repo,
-        &pull_request_details.base_ref,
-        &pull_request_details.base_sha,
-    )
-        .create()
-        .await?;
-
-    // create a branch for the head repository
-    Branch::new(
-        &fork_result.owner,
-        &fork_result.repo,
-        &pull_request_details.head_ref,
-        &pull_request_details.head_sha,
-    )
-        .create()
-        .await?;
-
-    // create a pull request
-    let new_pull_request = PullRequest::new(
-        &fork_result.owner,
-        &fork_result.repo,
-        Some(&pull_request_details.title),
-        Some(&pull_request_details.body),
-        &pull_request_details.base_ref,
-        &pull_request_details.head_ref,
-    );
-    let pull_request_result = new_pull_request.create().await?;
-    let pull_request_reviews = get_pull_request_reviews(
-        &fork.owner,
-        &fork.repo,
-        pull_request.pull_number.clone().unwrap().into(),
-    )
-        .await?;
-
-    let url = pull_request_result["html_url"]
-        .as_str()
-        .map(String::from)
-        .or_else(|| {
-            if pull_request_reviews.len() > 0 {
-                Some(format!(
-                    "https://github.com/{}/{}/pull/{}",
-                    &fork.owner, &fork.repo, &pr_number
-                ))
-            } else {
-                None
+    let config = GitHubClientConfig::for_host(&host)?;
+    let token = get_or_prompt_token(&host)
+        .await
+        .map_err(|_| FreshEyesError::MissingTokenError)?;
+    let client = GitHubClient::with_config(config)?.with_token(token);
+
+    let report = |progress: MirrorProgress| println!("{}...", progress);
+    // the CLI waits as long as GitHub takes to create a fork
+    let upstream = ForkRequest::new(&owner, &repo);
+
+    match command {
+        Command::Mirror { pr_number, blind } => {
+            // fork the repository and mirror the pull request into the fork
+            let mirror = mirror_pull_request(
+                &client,
+                &upstream,
+                pr_number,
+                blind,
+                MirrorStyle::Plain,
+                &report,
+            )
+            .await?;
+
+            if !mirror.created {
+                println!("This pull request has already been mirrored to your fork");
+            }
+            println!("Pull Request URL: {}", mirror.pr_url);
+        }
+        Command::Refresh { pr_number, blind } => {
+            let refresh = refresh_mirror(&client, &upstream, pr_number, blind, &report).await?;
+
+            print_branch_refresh(&refresh.base);
+            print_branch_refresh(&refresh.head);
+            if refresh.forced {
+                println!("The pull request was force-pushed, the mirror now shows the new history");
+            }
+        }
+        Command::Reveal {
+            pr_number,
+            resolved_threads,
+        } => {
+            let reveal = reveal(&client, &owner, &repo, pr_number, resolved_threads).await?;
+
+            print_reveal(&reveal);
+        }
+        Command::Cleanup { filter, dry_run } => {
+            let cleanup = cleanup_mirrors(&client, &owner, &repo, &filter, dry_run).await?;
+
+            print_cleanup(&cleanup);
+        }
+        Command::Batch {
+            mut pr_numbers,
+            label,
+            concurrency,
+            blind,
+        } => {
+            if let Some(label) = label {
+                for pr_number in find_pull_requests_by_label(&client, &owner, &repo, &label).await?
+                {
+                    if !pr_numbers.contains(&pr_number) {
+                        pr_numbers.push(pr_number);
+                    }
+                }
             }
-        });
+            if pr_numbers.is_empty() {
+                println!("No pull requests to mirror");
+                return Ok(());
+            }
+
+            let batch = mirror_pull_requests(
+                &client,
+                &upstream,
+                &pr_numbers,
+                concurrency,
+                blind,
+                MirrorStyle::Plain,
+                &|pr_number, progress| match pr_number {
+                    Some(pr_number) => println!("#{}: {}...", pr_number, progress),
+                    None => println!("{}...", progress),
+                },
+            )
+            .await?;
 
-    match url {
-        Some(pr_url) => {
-            println!("Pull Request URL: {}", pr_url);
+            print_batch(&batch);
         }
-        None => {
-            println!(
-                "{}",
-                pull_request_result["message"].as_str().unwrap_or_default()
-            );
-            println!("No reviews found for this pull request");
+        Command::List => {
+            let mirrors = list_mirrors(&client, &owner, &repo).await?;
+
+            print_mirrors(&mirrors);
         }
     }
 
     Ok(())
 }
 
+fn print_branch_refresh(refresh: &BranchRefresh) {
+    let old_sha = refresh.old_sha.as_deref().unwrap_or("nothing");
+    match refresh.update {
+        RefUpdate::Unchanged => println!("{} is up to date", refresh.branch),
+        RefUpdate::Created => println!("{} created at {}", refresh.branch, refresh.new_sha),
+        RefUpdate::FastForwarded => println!(
+            "{} fast-forwarded from {} to {}",
+            refresh.branch, old_sha, refresh.new_sha
+        ),
+        RefUpdate::ForceUpdated => println!(
+            "{} force-updated from {} to {}",
+            refresh.branch, old_sha, refresh.new_sha
+        ),
+    }
+}
+
+fn print_reveal(reveal: &RevealResult) {
+    if reveal.already_revealed {
+        println!(
+            "The comments were already revealed on {}",
+            reveal.mirror_pr_url
+        );
+        return;
+    }
+
+    if let Some(summary) = &reveal.summary {
+        println!("{}", summary);
+    }
+    for line in &reveal.lines {
+        println!(
+            "Revealed the comments on {} line {}{}",
+            line.path,
+            line.line,
+            if line.on_line {
+                ""
+            } else {
+                ", on the conversation as the line isn't in the mirror"
+            }
+        );
+    }
+    println!("Pull Request URL: {}", reveal.mirror_pr_url);
+}
+
+/// a summary table with a row per pull request
+fn print_batch(batch: &BatchResult) {
+    println!();
+    println!("{:<8} {:<9} MIRROR", "PR", "STATUS");
+    for mirror in &batch.mirrors {
+        let (status, detail) = match (&mirror.pr_url, &mirror.error) {
+            (Some(pr_url), _) if mirror.created => ("created", pr_url.as_str()),
+            (Some(pr_url), _) => ("existing", pr_url.as_str()),
+            (None, error) => ("failed", error.as_deref().unwrap_or_default()),
+        };
+        println!(
+            "{:<8} {:<9} {}",
+            format!("#{}", mirror.pull_number),
+            status,
+            detail
+        );
+    }
+
+    let failed = batch
+        .mirrors
+        .iter()
+        .filter(|mirror| mirror.error.is_some())
+        .count();
+    println!(
+        "\nMirrored {} of {} pull requests into {}",
+        batch.mirrors.len() - failed,
+        batch.mirrors.len(),
+        batch.fork.forked_repo
+    );
+}
+
+fn print_mirrors(mirrors: &[MirrorStatus]) {
+    if mirrors.is_empty() {
+        println!("You haven't mirrored any pull requests of this repository");
+        return;
+    }
+
+    for mirror in mirrors {
+        let upstream = format!(
+            "{}/{}#{}",
+            mirror.upstream_owner, mirror.upstream_repo, mirror.upstream_pull_number
+        );
+        match (&mirror.error, mirror.upstream_state) {
+            (None, Some(upstream_state)) => println!(
+                "{} ({:?}, {} review comments{}): {}",
+                upstream,
+                upstream_state,
+                mirror.upstream_review_comments,
+                if mirror.head_up_to_date == Some(false) {
+                    ", outdated, run refresh"
+                } else {
+                    ""
+                },
+                mirror.mirror_pr_url
+            ),
+            (error, _) => println!(
+                "{} (failed to fetch it: {}): {}",
+                upstream,
+                error.as_deref().unwrap_or("unknown error"),
+                mirror.mirror_pr_url
+            ),
+        }
+    }
+}
+
+fn print_cleanup(cleanup: &CleanupResult) {
+    if cleanup.mirrors.is_empty() {
+        println!("No mirrors to clean up in {}", cleanup.fork);
+        return;
+    }
+
+    let action = if cleanup.dry_run {
+        "Would clean up"
+    } else {
+        "Cleaned up"
+    };
+    for mirror in &cleanup.mirrors {
+        match (&mirror.error, mirror.upstream_state) {
+            (None, Some(upstream_state)) => println!(
+                "{} the mirror of #{} ({:?} upstream): {}{}",
+                action,
+                mirror.pull_number,
+                upstream_state,
+                mirror
+                    .mirror_pr_url
+                    .as_ref()
+                    .map(|url| format!("{}, ", url))
+                    .unwrap_or_default(),
+                mirror.branches.join(", ")
+            ),
+            (error, _) => println!(
+                "Failed to clean up the mirror of #{}: {}",
+                mirror.pull_number,
+                error.as_deref().unwrap_or("unknown error")
+            ),
+        }
+    }
+    if cleanup.dry_run {
+        println!("Dry run, nothing was changed in {}", cleanup.fork);
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let args = cli::run_cli();
@@ -96,4 +265,4 @@ async fn main() {
         println!("Error: {}", e);
         exit(1);
     }
-}
\ No newline at end of file
+}
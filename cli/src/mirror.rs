@@ -0,0 +1,689 @@
+use serde::Serialize;
+use std::fmt;
+
+use crate::{
+    extract_pr_details, models::required, Branch, CreateOutcome, ForkRequest, ForkResult,
+    FreshEyesError, GitHubClient, GitReference, MirrorStyle, PullRequest, PullRequestData,
+    PullRequestDetails, RequestMethod,
+};
+
+/// The mirror of an upstream pull request in the user's fork
+#[derive(Debug, Serialize)]
+pub struct MirrorResult {
+    pub fork: ForkResult,
+    /// url of the mirror pull request in the fork
+    pub pr_url: String,
+    /// number of the mirror pull request in the fork
+    pub pull_number: u64,
+    /// `false` when an earlier run already mirrored the pull request
+    pub created: bool,
+}
+
+/// A step of [`mirror_pull_request`], reported as it happens
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum MirrorProgress {
+    Forking {
+        upstream: String,
+    },
+    /// GitHub is still copying the repository into the fork
+    WaitingForFork {
+        fork: String,
+        attempt: u32,
+        waited_secs: u64,
+    },
+    ForkReady {
+        fork: String,
+    },
+    FetchingPullRequest {
+        pull_number: u32,
+    },
+    CreatingBranches {
+        base: String,
+        head: String,
+    },
+    CreatingPullRequest,
+    /// moving a mirror branch to the current commit of the upstream pull request
+    UpdatingBranch {
+        branch: String,
+    },
+}
+
+impl fmt::Display for MirrorProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Forking { upstream } => write!(f, "Forking {}", upstream),
+            Self::WaitingForFork {
+                fork,
+                attempt,
+                waited_secs,
+            } => write!(
+                f,
+                "Waiting for GitHub to finish creating {} (check {}, {}s elapsed)",
+                fork, attempt, waited_secs
+            ),
+            Self::ForkReady { fork } => write!(f, "Fork {} is ready", fork),
+            Self::FetchingPullRequest { pull_number } => {
+                write!(f, "Fetching pull request #{}", pull_number)
+            }
+            Self::CreatingBranches { base, head } => {
+                write!(f, "Creating branches {} and {}", base, head)
+            }
+            Self::CreatingPullRequest => write!(f, "Creating the pull request"),
+            Self::UpdatingBranch { branch } => write!(f, "Updating branch {}", branch),
+        }
+    }
+}
+
+/// Mirror a pull request into the user's fork: fork the repository, create the base and head
+/// branches and open a pull request between them, worded as `style` says.
+/// Running it again for the same pull request returns the mirror the first run created.
+/// With `blind` the mirror doesn't tell who wrote the pull request.
+/// Each step is reported to `progress`, waiting for a new fork can take minutes, up to the
+/// `ready_timeout` of `upstream`.
+pub async fn mirror_pull_request(
+    client: &GitHubClient,
+    upstream: &ForkRequest<'_>,
+    pull_number: u32,
+    blind: bool,
+    style: MirrorStyle,
+    progress: &(dyn Fn(MirrorProgress) + Send + Sync),
+) -> Result<MirrorResult, FreshEyesError> {
+    let (owner, repo) = (upstream.owner, upstream.repo);
+    // create a fork of the base repository and wait until GitHub has copied it
+    let fork = upstream.fork_with_progress(client, progress).await?;
+
+    mirror_into_fork(
+        client,
+        fork,
+        owner,
+        repo,
+        pull_number,
+        blind,
+        style,
+        progress,
+    )
+    .await
+}
+
+/// Mirror a pull request of `owner/repo` into a fork that is ready, the rest of
+/// [`mirror_pull_request`]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn mirror_into_fork(
+    client: &GitHubClient,
+    fork: ForkResult,
+    owner: &str,
+    repo: &str,
+    pull_number: u32,
+    blind: bool,
+    style: MirrorStyle,
+    progress: &(dyn Fn(MirrorProgress) + Send + Sync),
+) -> Result<MirrorResult, FreshEyesError> {
+    // fetch the desired pull request
+    progress(MirrorProgress::FetchingPullRequest { pull_number });
+    let pull_request = PullRequest::from_pull_number(owner, repo, pull_number)
+        .get(client)
+        .await?;
+    let mut details = extract_pr_details(&pull_request, blind, style)?;
+    details.head_sha = upstream_head_sha(client, &pull_request).await?;
+
+    // the branches can only point at commits the fork can see
+    ensure_commits_reachable(client, &fork, &pull_request, &details.head_sha).await?;
+
+    // create the base and head branches, they already exist when re-running
+    progress(MirrorProgress::CreatingBranches {
+        base: details.base_ref.clone(),
+        head: details.head_ref.clone(),
+    });
+    Branch::new(
+        &fork.owner,
+        &fork.repo,
+        &details.base_ref,
+        &details.base_sha,
+    )
+    .create(client)
+    .await?;
+    Branch::new(
+        &fork.owner,
+        &fork.repo,
+        &details.head_ref,
+        &details.head_sha,
+    )
+    .create(client)
+    .await?;
+
+    // create the mirror pull request, or look up the one an earlier run created
+    let new_pull_request = PullRequest::new(
+        &fork.owner,
+        &fork.repo,
+        Some(&details.title),
+        Some(&details.body),
+        &details.base_ref,
+        &details.head_ref,
+    );
+    progress(MirrorProgress::CreatingPullRequest);
+    let (mirror, created) = match new_pull_request.create(client).await? {
+        CreateOutcome::Created(mirror) => (mirror, true),
+        CreateOutcome::AlreadyExists(error_response) => {
+            match new_pull_request.find_open(client).await? {
+                Some(mirror) => (mirror, false),
+                // GitHub says it exists but we can't see it, report what GitHub said
+                None => return Err(FreshEyesError::Validation(error_response)),
+            }
+        }
+    };
+
+    Ok(MirrorResult {
+        fork,
+        pr_url: mirror.html_url,
+        pull_number: mirror.number,
+        created,
+    })
+}
+
+/// How [`refresh_mirror`] changed a mirror branch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefUpdate {
+    /// the branch already pointed at the upstream commit
+    Unchanged,
+    /// the base branch was missing and has been created
+    Created,
+    FastForwarded,
+    /// upstream rewrote history, e.g. a rebase, and the branch was force-updated to follow it
+    ForceUpdated,
+}
+
+/// A mirror branch before and after [`refresh_mirror`]
+#[derive(Debug, Serialize)]
+pub struct BranchRefresh {
+    pub branch: String,
+    /// `None` when the branch didn't exist
+    pub old_sha: Option<String>,
+    pub new_sha: String,
+    pub update: RefUpdate,
+}
+
+/// The mirror branches after [`refresh_mirror`]
+#[derive(Debug, Serialize)]
+pub struct RefreshResult {
+    pub fork: ForkResult,
+    pub base: BranchRefresh,
+    pub head: BranchRefresh,
+    /// `true` when either branch had to be force-updated
+    pub forced: bool,
+}
+
+/// Point the branches of an existing mirror at the current base and head of the upstream pull
+/// request, e.g. after the author force-pushed. The mirror pull request follows its branches.
+/// Fails with [`FreshEyesError::MirrorNotFound`] when the pull request hasn't been mirrored.
+/// `blind` has to be what the mirror was made with, blind mirrors name their head branch after
+/// the pseudonym of the author, so a mismatch is refused instead of creating a stray branch.
+pub async fn refresh_mirror(
+    client: &GitHubClient,
+    upstream: &ForkRequest<'_>,
+    pull_number: u32,
+    blind: bool,
+    progress: &(dyn Fn(MirrorProgress) + Send + Sync),
+) -> Result<RefreshResult, FreshEyesError> {
+    let (owner, repo) = (upstream.owner, upstream.repo);
+    // forking again returns the existing fork
+    let fork = upstream.fork_with_progress(client, progress).await?;
+
+    progress(MirrorProgress::FetchingPullRequest { pull_number });
+    let pull_request = PullRequest::from_pull_number(owner, repo, pull_number)
+        .get(client)
+        .await?;
+    // only the branches are refreshed, their names don't depend on the style
+    let mut details = extract_pr_details(&pull_request, blind, MirrorStyle::default())?;
+    check_mirror_exists(client, &fork, &pull_request, &details, blind).await?;
+    details.head_sha = upstream_head_sha(client, &pull_request).await?;
+
+    ensure_commits_reachable(client, &fork, &pull_request, &details.head_sha).await?;
+
+    let base = Branch::new(
+        &fork.owner,
+        &fork.repo,
+        &details.base_ref,
+        &details.base_sha,
+    );
+    let base = refresh_branch(client, &base, progress).await?;
+    let head = Branch::new(
+        &fork.owner,
+        &fork.repo,
+        &details.head_ref,
+        &details.head_sha,
+    );
+    let head = refresh_branch(client, &head, progress).await?;
+
+    let forced = base.update == RefUpdate::ForceUpdated || head.update == RefUpdate::ForceUpdated;
+    Ok(RefreshResult {
+        fork,
+        base,
+        head,
+        forced,
+    })
+}
+
+/// Make sure the pull request has a mirror to refresh: its head branch is there under the name
+/// `blind` gives it. A mirror made with the other `blind` is refused, refreshing it would create
+/// a stray branch next to it.
+async fn check_mirror_exists(
+    client: &GitHubClient,
+    fork: &ForkResult,
+    pull_request: &PullRequestData,
+    details: &PullRequestDetails,
+    blind: bool,
+) -> Result<(), FreshEyesError> {
+    let head = Branch::new(
+        &fork.owner,
+        &fork.repo,
+        &details.head_ref,
+        &details.head_sha,
+    );
+    if head.get(client).await?.is_some() {
+        return Ok(());
+    }
+    let mirror = format!("{}#{}", upstream_name(pull_request)?, pull_request.number);
+
+    // the pseudonym needs the upstream repository and the secret, and the head branch of
+    // someone other than the author has the same name either way
+    if let Ok(other) = extract_pr_details(pull_request, !blind, MirrorStyle::default()) {
+        let other_head = Branch::new(&fork.owner, &fork.repo, &other.head_ref, &other.head_sha);
+        if other.head_ref != details.head_ref && other_head.get(client).await?.is_some() {
+            return Err(FreshEyesError::BlindMismatch {
+                mirror,
+                blind: !blind,
+            });
+        }
+    }
+    Err(FreshEyesError::MirrorNotFound(mirror))
+}
+
+/// move the branch to its sha, fast-forwarding when possible and forcing otherwise
+pub(crate) async fn refresh_branch(
+    client: &GitHubClient,
+    branch: &Branch<'_>,
+    progress: &(dyn Fn(MirrorProgress) + Send + Sync),
+) -> Result<BranchRefresh, FreshEyesError> {
+    let old_sha = branch
+        .get(client)
+        .await?
+        .map(|reference| reference.object.sha);
+    if old_sha.as_deref() != Some(branch.sha) {
+        progress(MirrorProgress::UpdatingBranch {
+            branch: branch.branch_ref.to_string(),
+        });
+    }
+    let update = match &old_sha {
+        Some(old_sha) if old_sha == branch.sha => RefUpdate::Unchanged,
+        Some(_) => match branch.update(client, false).await {
+            Ok(_) => RefUpdate::FastForwarded,
+            Err(FreshEyesError::Validation(error_response))
+                if error_response.is_not_fast_forward() =>
+            {
+                branch.update(client, true).await?;
+                RefUpdate::ForceUpdated
+            }
+            Err(e) => return Err(e),
+        },
+        None => {
+            branch.create(client).await?;
+            RefUpdate::Created
+        }
+    };
+
+    Ok(BranchRefresh {
+        branch: branch.branch_ref.to_string(),
+        old_sha,
+        new_sha: branch.sha.to_string(),
+        update,
+    })
+}
+
+/// The commit the head branch of the mirror points at: the one upstream's `refs/pull/{n}/head`
+/// points at, which keeps the head commit of pull requests from third-party forks, even when the
+/// head repository was deleted. `head.sha` when upstream doesn't have the ref.
+pub(crate) async fn upstream_head_sha(
+    client: &GitHubClient,
+    pull_request: &PullRequestData,
+) -> Result<String, FreshEyesError> {
+    let head_sha = required(&pull_request.head.sha, "head.sha")?;
+    let reference =
+        upstream_pull_ref(client, upstream_name(pull_request)?, pull_request.number).await?;
+    Ok(reference.map_or_else(|| head_sha.to_string(), |reference| reference.object.sha))
+}
+
+/// Make sure the base and head commits of the pull request can be reached from the fork.
+/// A fork shares the objects of upstream, so its branches can point at any commit of upstream,
+/// including the ones only `refs/pull/{n}/head` points at. The branches of the user are left alone.
+pub(crate) async fn ensure_commits_reachable(
+    client: &GitHubClient,
+    fork: &ForkResult,
+    pull_request: &PullRequestData,
+    head_sha: &str,
+) -> Result<(), FreshEyesError> {
+    let shas = [pull_request.base.sha.as_str(), head_sha];
+    let Some(sha) = missing_commit(client, fork, &shas).await? else {
+        return Ok(());
+    };
+
+    // explain why the commit can't be reached
+    let full_name = format!("{}/{}", fork.owner, fork.repo);
+    let upstream = upstream_name(pull_request)?;
+    let pull_ref = format!("refs/pull/{}/head", pull_request.number);
+    let reason = if sha == pull_request.base.sha {
+        format!(
+            "it is not on {} of {}",
+            pull_request.base.ref_name, upstream
+        )
+    } else {
+        match upstream_pull_ref(client, upstream, pull_request.number).await? {
+            None => format!("{} does not have {}", upstream, pull_ref),
+            Some(reference) if reference.object.sha != sha => format!(
+                "{} of {} points at {}, the pull request was probably force-pushed, try again",
+                pull_ref, upstream, reference.object.sha
+            ),
+            Some(_) => format!(
+                "{} of {} points at it, but GitHub doesn't share it with {} yet, try again",
+                pull_ref, upstream, full_name
+            ),
+        }
+    };
+
+    Err(FreshEyesError::CommitUnreachable {
+        sha: sha.to_string(),
+        fork: full_name,
+        reason,
+    })
+}
+
+/// `owner/repo` of the repository the pull request was opened on
+fn upstream_name(pull_request: &PullRequestData) -> Result<&str, FreshEyesError> {
+    match &pull_request.base.repo {
+        Some(repo) => Ok(&repo.full_name),
+        // deleting the base repository deletes its pull requests, GitHub always sends it
+        None => Err(FreshEyesError::InvalidResponse(serde::de::Error::custom(
+            format!("{} has no base repository", pull_request.html_url),
+        ))),
+    }
+}
+
+/// the first of the commits the fork doesn't have
+async fn missing_commit<'a>(
+    client: &GitHubClient,
+    fork: &ForkResult,
+    shas: &[&'a str],
+) -> Result<Option<&'a str>, FreshEyesError> {
+    for sha in shas {
+        let commit = client
+            .fetch_github_data(
+                &format!("/repos/{}/{}/git/commits/{}", fork.owner, fork.repo, sha),
+                RequestMethod::GET,
+            )
+            .await;
+        match commit {
+            Ok(_) => {}
+            Err(FreshEyesError::NotFound(_)) | Err(FreshEyesError::Validation(_)) => {
+                return Ok(Some(sha))
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+/// `refs/pull/{n}/head` of the upstream repository, `None` if GitHub doesn't have it
+async fn upstream_pull_ref(
+    client: &GitHubClient,
+    upstream: &str,
+    pull_number: u64,
+) -> Result<Option<GitReference>, FreshEyesError> {
+    let reference = client
+        .fetch_github_data(
+            &format!("/repos/{}/git/ref/pull/{}/head", upstream, pull_number),
+            RequestMethod::GET,
+        )
+        .await;
+    match reference {
+        Ok(data) => Ok(Some(serde_json::from_value(data)?)),
+        Err(FreshEyesError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{mock_fork, pull_request_json, set_blind_secret, test_client};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_unreachable_head_commit_is_explained() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin/git/commits/base")
+            .with_body(r#"{"sha": "base"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin/git/commits/head")
+            .with_status(404)
+            .with_body(r#"{"message": "Not Found"}"#)
+            .create_async()
+            .await;
+        // the branches of the user are left alone
+        let sync = server
+            .mock("POST", "/repos/fresheyes/bitcoin/merge-upstream")
+            .expect(0)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/git/ref/pull/79/head")
+            .with_body(
+                r#"{"ref": "refs/pull/79/head", "url": "", "object": {"sha": "newer", "type": "commit", "url": ""}}"#,
+            )
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let pull_request: PullRequestData = serde_json::from_value(pull_request_json(
+            "bitcoin",
+            "bitcoin",
+            79,
+            "gavinandresen",
+            "rounding",
+        ))
+        .unwrap();
+        let fork = ForkResult::new(
+            "fresheyes".to_string(),
+            "bitcoin".to_string(),
+            "https://github.com/fresheyes/bitcoin".to_string(),
+        );
+
+        // the mirror follows refs/pull/79/head, which reaches commits of third-party forks
+        let head_sha = upstream_head_sha(&client, &pull_request).await.unwrap();
+        assert_eq!(head_sha, "newer");
+        let result = ensure_commits_reachable(&client, &fork, &pull_request, "head").await;
+
+        sync.assert_async().await;
+        match result {
+            Err(FreshEyesError::CommitUnreachable { sha, reason, .. }) => {
+                assert_eq!(sha, "head");
+                assert!(reason.contains("refs/pull/79/head of bitcoin/bitcoin points at newer"));
+            }
+            other => panic!("expected CommitUnreachable, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_branch_force_updates_rewritten_history() {
+        let mut server = mockito::Server::new_async().await;
+        let reference = |sha: &str| {
+            format!(
+                r#"{{"ref": "refs/heads/gavinandresen-fresheyes-rounding-79", "url": "", "object": {{"sha": "{}", "type": "commit", "url": ""}}}}"#,
+                sha
+            )
+        };
+        server
+            .mock(
+                "GET",
+                "/repos/fresheyes/bitcoin/git/ref/heads/gavinandresen-fresheyes-rounding-79",
+            )
+            .with_body(reference("old"))
+            .create_async()
+            .await;
+        let fast_forward = server
+            .mock(
+                "PATCH",
+                "/repos/fresheyes/bitcoin/git/refs/heads/gavinandresen-fresheyes-rounding-79",
+            )
+            .match_body(mockito::Matcher::PartialJson(
+                json!({"sha": "rebased", "force": false}),
+            ))
+            .with_status(422)
+            .with_body(r#"{"message": "Update is not a fast forward"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let force = server
+            .mock(
+                "PATCH",
+                "/repos/fresheyes/bitcoin/git/refs/heads/gavinandresen-fresheyes-rounding-79",
+            )
+            .match_body(mockito::Matcher::PartialJson(
+                json!({"sha": "rebased", "force": true}),
+            ))
+            .with_body(reference("rebased"))
+            .expect(1)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let branch = Branch::new(
+            "fresheyes",
+            "bitcoin",
+            "gavinandresen-fresheyes-rounding-79",
+            "rebased",
+        );
+        let refresh = refresh_branch(&client, &branch, &|_| {}).await.unwrap();
+
+        fast_forward.assert_async().await;
+        force.assert_async().await;
+        assert_eq!(refresh.update, RefUpdate::ForceUpdated);
+        assert_eq!(refresh.old_sha.as_deref(), Some("old"));
+        assert_eq!(refresh.new_sha, "rebased");
+    }
+
+    /// mock the fork `fresheyes/bitcoin`, ready, and the upstream pull request bitcoin/bitcoin#79
+    async fn mock_fork_and_pull_request(server: &mut mockito::Server) {
+        mock_fork(server).await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin")
+            .with_body(r#"{"name": "bitcoin"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin/git/refs/heads")
+            .with_body(r#"[{"ref": "refs/heads/master"}]"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/79")
+            .with_body(
+                pull_request_json("bitcoin", "bitcoin", 79, "gavinandresen", "rounding")
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_refresh_refuses_other_blind_mode() {
+        set_blind_secret();
+        let mut server = mockito::Server::new_async().await;
+        mock_fork_and_pull_request(&mut server).await;
+        // the mirror was made blind, its head branch is named after the pseudonym
+        server
+            .mock(
+                "GET",
+                "/repos/fresheyes/bitcoin/git/ref/heads/gavinandresen-fresheyes-rounding-79",
+            )
+            .with_status(404)
+            .with_body(r#"{"message": "Not Found"}"#)
+            .create_async()
+            .await;
+        server
+            .mock(
+                "GET",
+                "/repos/fresheyes/bitcoin/git/ref/heads/author-a7bbc9-fresheyes-rounding-79",
+            )
+            .with_body(
+                r#"{"ref": "refs/heads/author-a7bbc9-fresheyes-rounding-79", "url": "", "object": {"sha": "head", "type": "commit", "url": ""}}"#,
+            )
+            .create_async()
+            .await;
+        let create = server
+            .mock("POST", "/repos/fresheyes/bitcoin/git/refs")
+            .expect(0)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let refresh = refresh_mirror(
+            &client,
+            &ForkRequest::new("bitcoin", "bitcoin"),
+            79,
+            false,
+            &|_| {},
+        )
+        .await;
+
+        create.assert_async().await;
+        match refresh {
+            Err(FreshEyesError::BlindMismatch { mirror, blind }) => {
+                assert_eq!(mirror, "bitcoin/bitcoin#79");
+                assert!(blind);
+            }
+            other => panic!("expected BlindMismatch, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_without_mirror() {
+        let mut server = mockito::Server::new_async().await;
+        mock_fork_and_pull_request(&mut server).await;
+        server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/repos/fresheyes/bitcoin/git/ref/heads/".to_string()),
+            )
+            .with_status(404)
+            .with_body(r#"{"message": "Not Found"}"#)
+            .create_async()
+            .await;
+        let create = server
+            .mock("POST", "/repos/fresheyes/bitcoin/git/refs")
+            .expect(0)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let refresh = refresh_mirror(
+            &client,
+            &ForkRequest::new("bitcoin", "bitcoin"),
+            79,
+            false,
+            &|_| {},
+        )
+        .await;
+
+        create.assert_async().await;
+        match refresh {
+            Err(FreshEyesError::MirrorNotFound(mirror)) => {
+                assert_eq!(mirror, "bitcoin/bitcoin#79")
+            }
+            other => panic!("expected MirrorNotFound, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{FreshEyesError, UserFields};
+
+/// A repository as returned by `GET /repos/{owner}/{repo}` and `POST /repos/{owner}/{repo}/forks`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Repository {
+    pub name: String,
+    pub full_name: String,
+    pub owner: UserFields,
+    pub html_url: String,
+    #[serde(default)]
+    pub fork: bool,
+    pub default_branch: Option<String>,
+    /// the repository it was forked from, only `GET /repos/{owner}/{repo}` tells
+    pub parent: Option<Box<Repository>>,
+}
+
+/// The base or head of a pull request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequestRef {
+    /// `owner:branch`
+    pub label: String,
+    #[serde(rename = "ref")]
+    pub ref_name: String,
+    pub sha: String,
+    /// `None` when the account that owns the branch has been deleted
+    pub user: Option<UserFields>,
+    /// `None` when the repository the branch lives in has been deleted
+    pub repo: Option<Repository>,
+}
+
+/// A pull request as returned by `GET /repos/{owner}/{repo}/pulls/{pull_number}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequestData {
+    pub number: u64,
+    pub title: String,
+    pub body: Option<String>,
+    /// `open` or `closed`
+    pub state: String,
+    pub html_url: String,
+    pub user: UserFields,
+    pub base: PullRequestRef,
+    pub head: PullRequestRef,
+    pub created_at: String,
+    pub updated_at: String,
+    pub closed_at: Option<String>,
+    pub merged_at: Option<String>,
+    /// number of review comments, only returned for a single pull request, not in lists
+    pub review_comments: Option<u64>,
+}
+
+/// The object a git reference points at
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitObject {
+    pub sha: String,
+    #[serde(rename = "type")]
+    pub object_type: String,
+    pub url: String,
+}
+
+/// A git reference as returned by `POST /repos/{owner}/{repo}/git/refs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitReference {
+    /// the fully qualified name, e.g. `refs/heads/master`
+    #[serde(rename = "ref")]
+    pub ref_name: String,
+    pub url: String,
+    pub object: GitObject,
+}
+
+/// A comment on the conversation of an issue or pull request, as returned by
+/// `GET /repos/{owner}/{repo}/issues/{issue_number}/comments`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueComment {
+    pub id: u64,
+    pub body: Option<String>,
+    pub html_url: String,
+    pub created_at: String,
+    /// `None` when the account has been deleted
+    pub user: Option<UserFields>,
+}
+
+/// A submitted review, as returned by `GET /repos/{owner}/{repo}/pulls/{pull_number}/reviews`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Review {
+    pub id: u64,
+    /// empty for approvals without a comment
+    pub body: Option<String>,
+    pub state: ReviewState,
+    pub html_url: String,
+    /// `None` while the review is pending
+    pub submitted_at: Option<String>,
+    /// `None` when the account has been deleted
+    pub user: Option<UserFields>,
+}
+
+/// What a review says about the pull request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReviewState {
+    Approved,
+    ChangesRequested,
+    Commented,
+    /// an approval or change request that no longer counts
+    Dismissed,
+    /// not submitted yet, only its author sees it
+    Pending,
+}
+
+/// A commit of a pull request, as returned by `GET /repos/{owner}/{repo}/pulls/{pull_number}/commits`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequestCommit {
+    pub sha: String,
+    pub html_url: String,
+}
+
+/// A comment on a commit, as returned by `GET /repos/{owner}/{repo}/commits/{commit_sha}/comments`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitComment {
+    pub id: u64,
+    pub body: String,
+    pub commit_id: String,
+    /// `None` when the comment is on the whole commit rather than a line
+    pub path: Option<String>,
+    pub line: Option<u64>,
+    pub html_url: String,
+    pub created_at: String,
+    /// `None` when the account has been deleted
+    pub user: Option<UserFields>,
+}
+
+impl PullRequestRef {
+    /// login of the account the branch belongs to
+    pub fn owner_login(&self) -> Result<&str, FreshEyesError> {
+        match &self.user {
+            Some(user) if !user.login.is_empty() => Ok(&user.login),
+            _ => Err(FreshEyesError::ValueUndefinedError(format!(
+                "the owner of {}",
+                self.label
+            ))),
+        }
+    }
+}
+
+/// fail with a clear error instead of carrying an empty field into branch names
+pub(crate) fn required<'a>(value: &'a str, name: &str) -> Result<&'a str, FreshEyesError> {
+    if value.trim().is_empty() {
+        Err(FreshEyesError::ValueUndefinedError(name.to_string()))
+    } else {
+        Ok(value)
+    }
+}
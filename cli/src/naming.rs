@@ -0,0 +1,282 @@
+use crate::{FreshEyesError, MirrorBranch};
+
+/// How mirror branches are named unless `FRESHEYES_BRANCH_TEMPLATE` says otherwise
+pub const DEFAULT_BRANCH_TEMPLATE: &str = "{user}-fresheyes-{ref}-{number}";
+/// The default when `ENV` is `staging`, so staging mirrors don't collide with production ones
+pub const STAGING_BRANCH_TEMPLATE: &str = "{user}-fresheyes-{env}-{ref}-{number}";
+/// Longest branch name git accepts on most file systems, 255 bytes with `refs/heads/`
+pub const MAX_BRANCH_NAME_LEN: usize = 255 - "refs/heads/".len();
+/// Value of `{user}` or `{ref}` when nothing is left of it after sanitizing, e.g. a ref `...`
+const EMPTY_VALUE: &str = "_";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    User,
+    Ref,
+    Number,
+    Env,
+}
+
+/// The names of the branches a mirror creates in the fork, with the placeholders
+/// `{user}` for the owner of the upstream branch, `{ref}` for its name,
+/// `{number}` for the pull request number and `{env}` for the `ENV` the bot runs in.
+/// Values are sanitized so the names pass git's `check-ref-format`.
+#[derive(Debug, Clone)]
+pub struct BranchTemplate {
+    parts: Vec<Part>,
+    /// sanitized value of `{env}`
+    environment: String,
+}
+
+impl BranchTemplate {
+    /// Parse a template, it needs `{number}` to tell the mirrors of a repository apart, and
+    /// `{user}` and `{ref}` to tell the base and head branches of a mirror apart
+    pub fn new(template: &str, environment: &str) -> Result<Self, FreshEyesError> {
+        let invalid = |reason: &str| FreshEyesError::InvalidBranchTemplate {
+            template: template.to_string(),
+            reason: reason.to_string(),
+        };
+
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while !rest.is_empty() {
+            let Some(start) = rest.find(['{', '}']) else {
+                parts.push(Part::Literal(rest.to_string()));
+                break;
+            };
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let placeholder = rest[start..]
+                .strip_prefix('{')
+                .and_then(|placeholder| placeholder.split_once('}'));
+            let Some((name, after)) = placeholder else {
+                return Err(invalid("unbalanced { or }"));
+            };
+            parts.push(match name {
+                "user" => Part::User,
+                "ref" => Part::Ref,
+                "number" => Part::Number,
+                "env" => Part::Env,
+                _ => return Err(invalid(&format!("unknown placeholder {{{}}}", name))),
+            });
+            rest = after;
+        }
+        for (part, missing) in [
+            (Part::Number, "{number} is missing"),
+            (Part::User, "{user} is missing"),
+            (Part::Ref, "{ref} is missing"),
+        ] {
+            if !parts.contains(&part) {
+                return Err(invalid(missing));
+            }
+        }
+
+        let branch_template = Self {
+            parts,
+            environment: sanitize_component(environment),
+        };
+        // the literal parts have to be valid too
+        branch_template
+            .branch_name("user", "branch", 1)
+            .map_err(|e| match e {
+                FreshEyesError::InvalidBranchName { reason, .. } => invalid(&reason),
+                e => e,
+            })?;
+        Ok(branch_template)
+    }
+
+    /// The template in `FRESHEYES_BRANCH_TEMPLATE`, or else the default for `ENV`
+    pub fn from_env() -> Result<Self, FreshEyesError> {
+        let environment = std::env::var("ENV").unwrap_or_default();
+        let template = match std::env::var("FRESHEYES_BRANCH_TEMPLATE") {
+            Ok(template) if !template.trim().is_empty() => template,
+            _ if environment == "staging" => STAGING_BRANCH_TEMPLATE.to_string(),
+            _ => DEFAULT_BRANCH_TEMPLATE.to_string(),
+        };
+        Self::new(template.trim(), &environment)
+    }
+
+    /// The name of the mirror of branch `ref_name` of `user`.
+    /// The branch name is shortened when the name would be too long for git.
+    pub fn branch_name(
+        &self,
+        user: &str,
+        ref_name: &str,
+        pull_number: u64,
+    ) -> Result<String, FreshEyesError> {
+        let user = non_empty(sanitize_component(user));
+        let ref_name = non_empty(sanitize_component(ref_name));
+
+        let fixed_len = self.render(&user, "", pull_number).len();
+        let refs = self.parts.iter().filter(|part| **part == Part::Ref).count();
+        let ref_name = match MAX_BRANCH_NAME_LEN.checked_sub(fixed_len) {
+            Some(available) if refs > 0 && ref_name.len() > available / refs => {
+                let mut end = available / refs;
+                while !ref_name.is_char_boundary(end) {
+                    end -= 1;
+                }
+                non_empty(sanitize_component(&ref_name[..end]))
+            }
+            _ => ref_name,
+        };
+
+        let name = self.render(&user, &ref_name, pull_number);
+        match check_ref_format(&name) {
+            Ok(()) => Ok(name),
+            Err(reason) => Err(FreshEyesError::InvalidBranchName {
+                name,
+                reason: reason.to_string(),
+            }),
+        }
+    }
+
+    /// Recognize a mirror branch by its name.
+    /// Names of the default templates are recognized too, so mirrors made before the template
+    /// changed can still be listed and cleaned up.
+    pub fn parse<'a>(&self, name: &'a str) -> Option<MirrorBranch<'a>> {
+        let empty = MirrorBranch {
+            user: "",
+            branch: "",
+            pull_number: 0,
+        };
+        self.match_parts(&self.parts, name, empty)
+            .or_else(|| MirrorBranch::parse(name))
+    }
+
+    fn render(&self, user: &str, ref_name: &str, pull_number: u64) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(literal) => literal.clone(),
+                Part::User => user.to_string(),
+                Part::Ref => ref_name.to_string(),
+                Part::Number => pull_number.to_string(),
+                Part::Env => self.environment.clone(),
+            })
+            .collect()
+    }
+
+    /// match the name against the parts, placeholders taking the shortest value that fits
+    fn match_parts<'a>(
+        &self,
+        parts: &[Part],
+        name: &'a str,
+        found: MirrorBranch<'a>,
+    ) -> Option<MirrorBranch<'a>> {
+        let Some((part, rest)) = parts.split_first() else {
+            return name.is_empty().then_some(found);
+        };
+        let value_len = match part {
+            Part::Literal(literal) => {
+                return self.match_parts(rest, name.strip_prefix(literal.as_str())?, found)
+            }
+            Part::Env => {
+                return self.match_parts(rest, name.strip_prefix(self.environment.as_str())?, found)
+            }
+            Part::Number => name.bytes().take_while(u8::is_ascii_digit).count(),
+            Part::User | Part::Ref => name.len(),
+        };
+
+        (1..=value_len)
+            .filter(|end| name.is_char_boundary(*end))
+            .find_map(|end| {
+                let (value, remaining) = name.split_at(end);
+                let mut found = found;
+                match part {
+                    Part::User => found.user = value,
+                    Part::Ref => found.branch = value,
+                    _ => found.pull_number = value.parse().ok()?,
+                }
+                self.match_parts(rest, remaining, found)
+            })
+    }
+}
+
+/// Why git would refuse `name` as a branch name, following `git check-ref-format --branch`
+pub fn check_ref_format(name: &str) -> Result<(), &'static str> {
+    if name.is_empty() {
+        return Err("it is empty");
+    }
+    if name.len() > MAX_BRANCH_NAME_LEN {
+        return Err("it is too long");
+    }
+    if name == "@" {
+        return Err("it is @");
+    }
+    if name.starts_with('-') {
+        return Err("it starts with -");
+    }
+    if name.contains("..") {
+        return Err("it contains ..");
+    }
+    if name.contains("@{") {
+        return Err("it contains @{");
+    }
+    if name.chars().any(is_forbidden) {
+        return Err("it contains a space, a control character or one of ~^:?*[\\");
+    }
+    if name.starts_with('/') || name.ends_with('/') || name.contains("//") {
+        return Err("it has an empty path component");
+    }
+    if name.ends_with('.') {
+        return Err("it ends with .");
+    }
+    if name
+        .split('/')
+        .any(|component| component.starts_with('.') || component.ends_with(".lock"))
+    {
+        return Err("a path component starts with . or ends with .lock");
+    }
+    Ok(())
+}
+
+fn is_forbidden(c: char) -> bool {
+    c.is_ascii_control() || matches!(c, ' ' | '~' | '^' | ':' | '?' | '*' | '[' | '\\')
+}
+
+/// Characters git allows but that would have to be escaped in the urls and queries the branch
+/// name ends up in, e.g. `head=owner:branch&base=master` or `git/ref/heads/branch`
+fn is_reserved_in_url(c: char) -> bool {
+    matches!(c, '#' | '&' | '%' | '+')
+}
+
+/// A value for a placeholder that can't break the name: slashes, characters git doesn't allow
+/// and characters reserved in urls become `-`, `..` becomes `.`, and leading dots and trailing
+/// dots and `.lock` go away
+fn sanitize_component(value: &str) -> String {
+    let mut sanitized: String = value
+        .chars()
+        .map(|c| {
+            if c == '/' || is_forbidden(c) || is_reserved_in_url(c) {
+                '-'
+            } else {
+                c
+            }
+        })
+        .collect();
+    while sanitized.contains("..") {
+        sanitized = sanitized.replace("..", ".");
+    }
+    sanitized = sanitized.replace("@{", "@-");
+
+    let mut trimmed = sanitized.as_str();
+    loop {
+        let before = trimmed;
+        trimmed = trimmed.trim_start_matches('.').trim_end_matches('.');
+        trimmed = trimmed.strip_suffix(".lock").unwrap_or(trimmed);
+        if trimmed == before {
+            return trimmed.to_string();
+        }
+    }
+}
+
+/// Keep a placeholder from disappearing, the name couldn't be parsed back without it
+fn non_empty(sanitized: String) -> String {
+    if sanitized.is_empty() {
+        EMPTY_VALUE.to_string()
+    } else {
+        sanitized
+    }
+}
@@ -0,0 +1,134 @@
+use reqwest::{header::HeaderMap, StatusCode};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Number of times a request is retried after a rate limit or server error response.
+pub const MAX_RETRIES: u32 = 3;
+/// Delay before the first retry when GitHub does not tell us how long to wait.
+/// Every further retry doubles it.
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Longest we are willing to sleep for a rate limit to reset before giving up.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// The rate limit state GitHub reports in the headers of every response.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    /// `X-RateLimit-Limit`: requests allowed per hour
+    pub limit: Option<u64>,
+    /// `X-RateLimit-Remaining`: requests left in the current window
+    pub remaining: Option<u64>,
+    /// `X-RateLimit-Reset`: unix time (seconds) at which the window resets
+    pub reset_at: Option<u64>,
+    /// `Retry-After`: seconds to wait before retrying, sent with secondary rate limits
+    pub retry_after: Option<u64>,
+}
+
+impl RateLimit {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let header_value = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok())
+        };
+
+        Self {
+            limit: header_value("x-ratelimit-limit"),
+            remaining: header_value("x-ratelimit-remaining"),
+            reset_at: header_value("x-ratelimit-reset"),
+            retry_after: header_value("retry-after"),
+        }
+    }
+
+    /// whether the primary rate limit has been used up
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining == Some(0)
+    }
+
+    /// whether a response with this status, these headers and this body was rejected by a
+    /// primary or secondary rate limit, as opposed to e.g. a 403 for missing permissions.
+    /// GitHub doesn't always send `Retry-After` with a secondary rate limit, only its message
+    /// tells it apart then.
+    pub fn is_rate_limited(&self, status: StatusCode, body: &str) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS
+            || (status == StatusCode::FORBIDDEN
+                && (self.is_exhausted()
+                    || self.retry_after.is_some()
+                    || body.to_lowercase().contains("secondary rate limit")))
+    }
+
+    /// how long GitHub asked us to wait, if it said so
+    fn requested_wait(&self) -> Option<Duration> {
+        if let Some(retry_after) = self.retry_after {
+            return Some(Duration::from_secs(retry_after));
+        }
+        if self.is_exhausted() {
+            let reset_at = self.reset_at?;
+            return Some(Duration::from_secs(reset_at.saturating_sub(unix_now())));
+        }
+        None
+    }
+
+    /// unix time at which it is safe to retry
+    pub fn retry_at(&self) -> u64 {
+        match self.requested_wait() {
+            Some(wait) => unix_now() + wait.as_secs(),
+            None => self.reset_at.unwrap_or_else(unix_now),
+        }
+    }
+}
+
+/// What to do after a non-successful response.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RetryDecision {
+    /// sleep for the given duration, then send the request again
+    Retry(Duration),
+    /// rate limited for longer than we are willing to wait
+    GiveUp { reset_at: u64 },
+    /// the error is not transient, report it to the caller
+    Fail,
+}
+
+/// Decide whether a failed request should be retried.
+/// Rate limited requests wait for as long as GitHub asks (up to a minute), falling back to an
+/// exponential backoff. Server errors are retried with an exponential backoff, but only for
+/// `idempotent` requests: a POST or PATCH may have gone through before the server failed, and
+/// sending it again would e.g. open a second mirror pull request.
+pub fn retry_decision(
+    status: StatusCode,
+    rate_limit: &RateLimit,
+    body: &str,
+    attempt: u32,
+    idempotent: bool,
+) -> RetryDecision {
+    if rate_limit.is_rate_limited(status, body) {
+        if attempt >= MAX_RETRIES {
+            return RetryDecision::GiveUp {
+                reset_at: rate_limit.retry_at(),
+            };
+        }
+        return match rate_limit.requested_wait() {
+            Some(wait) if wait > MAX_RATE_LIMIT_WAIT => RetryDecision::GiveUp {
+                reset_at: rate_limit.retry_at(),
+            },
+            Some(wait) => RetryDecision::Retry(wait),
+            None => RetryDecision::Retry(backoff(attempt)),
+        };
+    }
+
+    if idempotent && status.is_server_error() && attempt < MAX_RETRIES {
+        return RetryDecision::Retry(backoff(attempt));
+    }
+
+    RetryDecision::Fail
+}
+
+fn backoff(attempt: u32) -> Duration {
+    BASE_RETRY_DELAY * 2u32.pow(attempt)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
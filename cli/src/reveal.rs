@@ -0,0 +1,696 @@
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::{
+    discussion::timestamp, get_issue_comments, get_review_thread_states, BranchTemplate,
+    Discussion, DiscussionComment, ForkRequest, ForkResult, FreshEyesError, GitHubClient,
+    PullRequest, PullRequestData, RequestMethod, ReviewThread, UserFields,
+};
+
+/// Ends the summary comment, so a second reveal doesn't post everything again
+const REVEAL_MARKER: &str = "<!-- fresheyes:reveal -->";
+/// Bots that post as regular users
+const KNOWN_BOTS: &[&str] = &["DrahtBot"];
+
+/// What [`reveal`] does with the review threads resolved upstream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolvedThreads {
+    /// like the other threads
+    #[default]
+    Show,
+    /// only the first comment, with the number of replies
+    Collapse,
+    /// leave them out, resolved nits are the noisiest part of a long review
+    Hide,
+}
+
+/// A comment [`reveal`] posts on the mirror pull request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RevealComment {
+    /// on the conversation, the summary of the upstream conversation
+    Issue { body: String },
+    /// on a line of the diff, linking the upstream review comments on that line
+    Review {
+        body: String,
+        commit_id: String,
+        path: String,
+        /// `LEFT` or `RIGHT`
+        side: String,
+        line: u64,
+        /// when the first upstream comment on the line was made
+        created_at: String,
+    },
+}
+
+/// A line of the diff [`reveal`] posted upstream review comments on
+#[derive(Debug, Serialize)]
+pub struct RevealedLine {
+    pub path: String,
+    pub line: u64,
+    /// `false` when the line isn't in the diff of the mirror, e.g. the mirror is behind upstream,
+    /// and the comment went on the conversation instead
+    pub on_line: bool,
+}
+
+/// What [`reveal`] posted on the mirror pull request
+#[derive(Debug, Serialize)]
+pub struct RevealResult {
+    pub mirror_pr_url: String,
+    /// the summary of the upstream conversation
+    pub summary: Option<String>,
+    pub lines: Vec<RevealedLine>,
+    /// an earlier reveal already posted the discussion, nothing was posted this time
+    pub already_revealed: bool,
+}
+
+/// Post the discussion of the upstream pull request on its mirror in the user's fork, once the
+/// reviewer is done reviewing with fresh eyes: a summary of the conversation, then links to the
+/// review comments of every line, in the order they were first commented on
+pub async fn reveal(
+    client: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    pull_number: u32,
+    resolved_threads: ResolvedThreads,
+) -> Result<RevealResult, FreshEyesError> {
+    let (fork, mirror) = find_mirror(client, owner, repo, pull_number as u64).await?;
+    let mirror_comments =
+        get_issue_comments(client, &fork.owner, &fork.repo, mirror.number).await?;
+    if mirror_comments.iter().any(|comment| {
+        comment
+            .body
+            .as_deref()
+            .is_some_and(|body| body.contains(REVEAL_MARKER))
+    }) {
+        return Ok(RevealResult {
+            mirror_pr_url: mirror.html_url,
+            summary: None,
+            lines: Vec::new(),
+            already_revealed: true,
+        });
+    }
+
+    let upstream = PullRequest::from_pull_number(owner, repo, pull_number)
+        .get(client)
+        .await?;
+    let mut discussion = Discussion::fetch(client, owner, repo, pull_number as u64).await?;
+    if resolved_threads != ResolvedThreads::Show {
+        // only the GraphQL API knows which threads were resolved
+        let states = get_review_thread_states(client, owner, repo, pull_number as u64).await?;
+        discussion.mark_resolved(&states);
+    }
+
+    let mut result = RevealResult {
+        mirror_pr_url: mirror.html_url,
+        summary: None,
+        lines: Vec::new(),
+        already_revealed: false,
+    };
+    let comments = plan_reveal(&discussion, &upstream.user.login, resolved_threads);
+    // one at a time, so the comments show up in order
+    for comment in comments {
+        match comment {
+            RevealComment::Issue { body } => {
+                let marked = format!("{}\n\n{}", body, REVEAL_MARKER);
+                post_issue_comment(client, &fork.owner, &fork.repo, mirror.number, &marked).await?;
+                result.summary = Some(body);
+            }
+            RevealComment::Review {
+                body,
+                commit_id,
+                path,
+                side,
+                line,
+                ..
+            } => {
+                let fetch_params = format!(
+                    "/repos/{}/{}/pulls/{}/comments",
+                    fork.owner, fork.repo, mirror.number
+                );
+                let value = json!({
+                    "body": body,
+                    "commit_id": commit_id,
+                    "path": path,
+                    "side": side,
+                    "line": line,
+                });
+                let on_line = match client
+                    .fetch_github_data(&fetch_params, RequestMethod::POST(value))
+                    .await
+                {
+                    Ok(_) => true,
+                    // the line or commit isn't part of the mirror's diff
+                    Err(FreshEyesError::Validation(_)) => {
+                        let body = format!("On `{}` line {}:\n\n{}", path, line, body);
+                        post_issue_comment(client, &fork.owner, &fork.repo, mirror.number, &body)
+                            .await?;
+                        false
+                    }
+                    Err(e) => return Err(e),
+                };
+                result.lines.push(RevealedLine {
+                    path,
+                    line,
+                    on_line,
+                });
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// The comments revealing the upstream discussion, in the order to post them: the summary of
+/// the conversation, then a comment per commented line with its threads, ordered by its first
+/// comment. Review comments on lines that no longer exist count towards the conversation.
+pub fn plan_reveal(
+    discussion: &Discussion,
+    pr_author: &str,
+    resolved_threads: ResolvedThreads,
+) -> Vec<RevealComment> {
+    let threads = discussion.review_threads();
+    let mut lines: BTreeMap<(&str, u64, &str), Vec<&ReviewThread>> = BTreeMap::new();
+    let mut outdated = HashSet::new();
+    for thread in &threads {
+        if thread.resolved && resolved_threads == ResolvedThreads::Hide {
+            continue;
+        }
+        match thread.line {
+            Some(line) => lines
+                .entry((thread.path.as_str(), line, thread.side.as_str()))
+                .or_default()
+                .push(thread),
+            // the line is gone since the pull request was rebased or force-pushed
+            None => outdated.extend(thread.comments().map(|comment| comment.id)),
+        }
+    }
+    let conversation: Vec<&DiscussionComment> = discussion
+        .comments
+        .iter()
+        .filter(|comment| match comment {
+            DiscussionComment::ReviewComment(comment) => outdated.contains(&comment.id),
+            _ => true,
+        })
+        .collect();
+
+    // always there, it carries the marker of the reveal
+    let mut comments = vec![RevealComment::Issue {
+        body: summarize_conversation(&conversation, pr_author),
+    }];
+
+    let mut line_comments: Vec<RevealComment> = lines
+        .into_iter()
+        .map(|((path, line, side), group)| {
+            // threads on a line are in the order they started
+            let first = &group[0].root;
+            let authors: HashSet<String> = group
+                .iter()
+                .flat_map(|thread| thread.comments())
+                .map(|comment| comment.user.login.to_lowercase())
+                .collect();
+            let links: Vec<String> = group
+                .iter()
+                .flat_map(|thread| {
+                    let link = format!(
+                        "- comment link `{}` at {}",
+                        thread.root.html_url,
+                        format_time(&thread.root.created_at)
+                    );
+                    let collapsed =
+                        thread.resolved && resolved_threads == ResolvedThreads::Collapse;
+                    let link = match thread.replies.len() {
+                        _ if !collapsed => link,
+                        0 => format!("{}, resolved", link),
+                        1 => format!("{}, resolved with 1 reply", link),
+                        replies => format!("{}, resolved with {} replies", link, replies),
+                    };
+                    let replies = if collapsed {
+                        &[][..]
+                    } else {
+                        &thread.replies[..]
+                    };
+                    std::iter::once(link).chain(replies.iter().map(|reply| {
+                        format!(
+                            "  - reply link `{}` at {}",
+                            reply.html_url,
+                            format_time(&reply.created_at)
+                        )
+                    }))
+                })
+                .collect();
+            RevealComment::Review {
+                body: format!(
+                    "{} commented here with:\n\n{}.",
+                    if authors.len() == 1 {
+                        "An author".to_string()
+                    } else {
+                        format!("{} authors", authors.len())
+                    },
+                    links.join("\n")
+                ),
+                commit_id: first.commit_id.clone(),
+                path: path.to_string(),
+                side: side.to_string(),
+                line,
+                created_at: first.created_at.clone(),
+            }
+        })
+        .collect();
+    line_comments.sort_by_key(|comment| match comment {
+        RevealComment::Review { created_at, .. } => timestamp(created_at),
+        RevealComment::Issue { .. } => 0,
+    });
+    comments.extend(line_comments);
+    comments
+}
+
+/// e.g. "There were 5 comments left by 2 reviewers, 1 bot and the author for this pull request"
+fn summarize_conversation(conversation: &[&DiscussionComment], pr_author: &str) -> String {
+    let login = |user: Option<&UserFields>| {
+        user.map_or("ghost".to_string(), |user| user.login.to_lowercase())
+    };
+    let is_bot = |user: Option<&UserFields>| {
+        user.is_some_and(|user| {
+            user.user_type
+                .as_deref()
+                .is_some_and(|user_type| user_type.eq_ignore_ascii_case("bot"))
+                || KNOWN_BOTS
+                    .iter()
+                    .any(|bot| bot.eq_ignore_ascii_case(&user.login))
+        })
+    };
+    let is_author = |user: Option<&UserFields>| login(user) == pr_author.to_lowercase();
+
+    let with_body = || {
+        conversation
+            .iter()
+            .filter(|comment| !comment.body().trim().is_empty())
+    };
+    let comments = with_body().count();
+    // reviewers who only approved, without a word, aren't counted
+    let reviewers: HashSet<String> = with_body()
+        .filter(|comment| !is_bot(comment.user()) && !is_author(comment.user()))
+        .map(|comment| login(comment.user()))
+        .collect();
+    let bots: HashSet<String> = with_body()
+        .filter(|comment| is_bot(comment.user()))
+        .map(|comment| login(comment.user()))
+        .collect();
+    let author = with_body().any(|comment| is_author(comment.user()));
+
+    let plural = |count: usize, noun: &str| {
+        format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+    };
+    let mut commenters = Vec::new();
+    if !reviewers.is_empty() {
+        commenters.push(plural(reviewers.len(), "reviewer"));
+    }
+    if !bots.is_empty() {
+        commenters.push(plural(bots.len(), "bot"));
+    }
+    if author {
+        commenters.push("the author".to_string());
+    }
+
+    match commenters.split_last() {
+        None => "There were no comments on the conversation of this pull request".to_string(),
+        Some((last, [])) => format!(
+            "There {} {} left by {} for this pull request",
+            if comments == 1 { "was" } else { "were" },
+            plural(comments, "comment"),
+            last
+        ),
+        Some((last, rest)) => format!(
+            "There were {} left by {} and {} for this pull request",
+            plural(comments, "comment"),
+            rest.join(", "),
+            last
+        ),
+    }
+}
+
+async fn post_issue_comment(
+    client: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    issue_number: u64,
+    body: &str,
+) -> Result<(), FreshEyesError> {
+    let fetch_params = format!("/repos/{}/{}/issues/{}/comments", owner, repo, issue_number);
+    client
+        .fetch_github_data(&fetch_params, RequestMethod::POST(json!({ "body": body })))
+        .await?;
+    Ok(())
+}
+
+/// the user's fork of `owner/repo` and the open mirror of the pull request in it
+async fn find_mirror(
+    client: &GitHubClient,
+    owner: &str,
+    repo: &str,
+    pull_number: u64,
+) -> Result<(ForkResult, PullRequestData), FreshEyesError> {
+    let template = BranchTemplate::from_env()?;
+    let mirror = format!("{}/{}#{}", owner, repo, pull_number);
+    let Some(fork) = ForkRequest::new(owner, repo).find(client).await? else {
+        return Err(FreshEyesError::MirrorNotFound(mirror));
+    };
+    let pulls = client
+        .fetch_github_data_paginated(&format!(
+            "/repos/{}/{}/pulls?state=open",
+            fork.owner, fork.repo
+        ))
+        .await?;
+    for pull in pulls {
+        let mirror: PullRequestData = serde_json::from_value(pull)?;
+        if template
+            .parse(&mirror.head.ref_name)
+            .is_some_and(|branch| branch.pull_number == pull_number)
+        {
+            return Ok((fork, mirror));
+        }
+    }
+    Err(FreshEyesError::MirrorNotFound(mirror))
+}
+
+/// e.g. `2011/02/08, 19:37:11 UTC`
+fn format_time(time: &str) -> String {
+    DateTime::parse_from_rfc3339(time).map_or(time.to_string(), |time| {
+        time.naive_utc()
+            .format("%Y/%m/%d, %H:%M:%S UTC")
+            .to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{mock_existing_fork, pull_request_json, review_comment, test_client};
+    use crate::{CommitComment, IssueComment, Review, ReviewComment, ReviewState};
+
+    #[test]
+    fn test_plan_reveal() {
+        let mut reply = review_comment(1, "sipa", "src/main.cpp", Some(10), "2011-02-08T21:00:00Z");
+        reply["in_reply_to_id"] = json!(2);
+        let review_comments: Vec<ReviewComment> = serde_json::from_value(json!([
+            reply,
+            review_comment(
+                2,
+                "laanwj",
+                "src/main.cpp",
+                Some(10),
+                "2011-02-08T20:00:00Z"
+            ),
+            review_comment(3, "sipa", "src/util.h", Some(3), "2011-02-08T19:40:00Z"),
+            review_comment(4, "sipa", "src/main.cpp", None, "2011-02-08T19:38:00Z"),
+        ]))
+        .unwrap();
+        let issue_comments: Vec<IssueComment> = serde_json::from_value(json!([
+            {"id": 5, "body": "Conflicts", "html_url": "https://github.com/bitcoin/bitcoin/pull/79#issuecomment-5", "created_at": "2011-02-08T19:39:00Z", "user": {"login": "DrahtBot", "type": "User"}},
+            {"id": 6, "body": "Thanks!", "html_url": "https://github.com/bitcoin/bitcoin/pull/79#issuecomment-6", "created_at": "2011-02-09T10:00:00Z", "user": {"login": "gavinandresen", "type": "User"}},
+        ]))
+        .unwrap();
+        let reviews: Vec<Review> = serde_json::from_value(json!([
+            {"id": 7, "body": "", "state": "APPROVED", "html_url": "https://github.com/bitcoin/bitcoin/pull/79#pullrequestreview-7", "submitted_at": "2011-02-09T11:00:00Z", "user": {"login": "laanwj"}},
+            {"id": 8, "body": "Concept ACK", "state": "COMMENTED", "html_url": "https://github.com/bitcoin/bitcoin/pull/79#pullrequestreview-8", "submitted_at": "2011-02-09T12:00:00Z", "user": {"login": "jonasschnelli"}},
+            {"id": 9, "body": "Draft", "state": "PENDING", "html_url": "https://github.com/bitcoin/bitcoin/pull/79#pullrequestreview-9", "submitted_at": null, "user": {"login": "jonasschnelli"}},
+        ]))
+        .unwrap();
+        let commit_comments: Vec<CommitComment> = serde_json::from_value(json!([
+            {"id": 10, "body": "Typo in the message", "commit_id": "8a9cad44a57f1e0057c127ced5078d7e722b9cc8", "path": null, "line": null, "html_url": "https://github.com/bitcoin/bitcoin/commit/8a9cad44a57f1e0057c127ced5078d7e722b9cc8#commitcomment-10", "created_at": "2011-02-08T19:37:30Z", "user": {"login": "luke-jr"}},
+        ]))
+        .unwrap();
+
+        let discussion = Discussion::new(
+            review_comments.clone(),
+            issue_comments,
+            reviews.clone(),
+            commit_comments,
+        );
+        // oldest first, without the pending review
+        let order: Vec<&str> = discussion
+            .comments
+            .iter()
+            .map(|comment| comment.html_url().rsplit(['#', '/']).next().unwrap())
+            .collect();
+        assert_eq!(
+            order,
+            [
+                "commitcomment-10",
+                "discussion_r4",
+                "issuecomment-5",
+                "discussion_r3",
+                "discussion_r2",
+                "discussion_r1",
+                "issuecomment-6",
+                "pullrequestreview-7",
+                "pullrequestreview-8",
+            ]
+        );
+        assert!(matches!(
+            &discussion.comments[7],
+            DiscussionComment::Review(Review {
+                state: ReviewState::Approved,
+                ..
+            })
+        ));
+
+        let plan = plan_reveal(&discussion, "gavinandresen", ResolvedThreads::Show);
+
+        assert_eq!(plan.len(), 3);
+        assert_eq!(
+            plan[0],
+            RevealComment::Issue {
+                body: "There were 5 comments left by 3 reviewers, 1 bot and the author for this pull request".to_string()
+            }
+        );
+        // ordered by the first comment on the line
+        match &plan[1] {
+            RevealComment::Review { path, line, .. } => {
+                assert_eq!((path.as_str(), *line), ("src/util.h", 3))
+            }
+            other => panic!("expected a review comment, got {:?}", other),
+        }
+        match &plan[2] {
+            RevealComment::Review {
+                body,
+                path,
+                line,
+                side,
+                created_at,
+                ..
+            } => {
+                assert_eq!(
+                    (path.as_str(), *line, side.as_str()),
+                    ("src/main.cpp", 10, "RIGHT")
+                );
+                assert_eq!(created_at, "2011-02-08T20:00:00Z");
+                assert_eq!(
+                    body,
+                    "2 authors commented here with:\n\n\
+                     - comment link `https://github.com/bitcoin/bitcoin/pull/79#discussion_r2` at 2011/02/08, 20:00:00 UTC\n\
+                     \x20 - reply link `https://github.com/bitcoin/bitcoin/pull/79#discussion_r1` at 2011/02/08, 21:00:00 UTC."
+                );
+            }
+            other => panic!("expected a review comment, got {:?}", other),
+        }
+
+        let discussion = Discussion::new(
+            review_comments[3..].to_vec(),
+            Vec::new(),
+            reviews[..1].to_vec(),
+            Vec::new(),
+        );
+        let plan = plan_reveal(&discussion, "gavinandresen", ResolvedThreads::Show);
+        assert_eq!(
+            plan,
+            [RevealComment::Issue {
+                body: "There was 1 comment left by 1 reviewer for this pull request".to_string()
+            }]
+        );
+        let discussion = Discussion::new(Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let plan = plan_reveal(&discussion, "gavinandresen", ResolvedThreads::Show);
+        assert_eq!(
+            plan,
+            [RevealComment::Issue {
+                body: "There were no comments on the conversation of this pull request".to_string()
+            }]
+        );
+    }
+
+    /// the user's renamed fork with the mirror of bitcoin/bitcoin#79 and its comments
+    async fn mock_mirror(server: &mut mockito::Server, mirror_comments: serde_json::Value) {
+        mock_existing_fork(server).await;
+        let mut mirror = pull_request_json(
+            "fresheyes",
+            "bitcoin-fork",
+            5,
+            "fresheyes",
+            "gavinandresen-fresheyes-rounding-79",
+        );
+        mirror["base"]["ref"] = json!("bitcoin-fresheyes-master-79");
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin-fork/pulls")
+            .match_query(mockito::Matcher::Any)
+            .with_body(json!([mirror]).to_string())
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin-fork/issues/5/comments")
+            .match_query(mockito::Matcher::Any)
+            .with_body(mirror_comments.to_string())
+            .create_async()
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_reveal() {
+        let mut server = mockito::Server::new_async().await;
+        mock_mirror(&mut server, json!([])).await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/79")
+            .with_body(
+                pull_request_json("bitcoin", "bitcoin", 79, "gavinandresen", "rounding")
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/79/comments")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                json!([
+                    review_comment(1, "sipa", "src/main.cpp", Some(10), "2011-02-08T20:00:00Z"),
+                    review_comment(2, "sipa", "src/gone.cpp", Some(1), "2011-02-08T21:00:00Z"),
+                ])
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/pulls/79/commits")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"[{"sha": "def", "html_url": "https://github.com/bitcoin/bitcoin/commit/def"}]"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repos/bitcoin/bitcoin/commits/def/comments")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                json!([{"id": 3, "body": "Typo", "commit_id": "def", "path": null, "line": null, "html_url": "https://github.com/bitcoin/bitcoin/commit/def#commitcomment-3", "created_at": "2011-02-08T19:40:00Z", "user": {"login": "luke-jr"}}])
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+        for path in [
+            "/repos/bitcoin/bitcoin/issues/79/comments",
+            "/repos/bitcoin/bitcoin/pulls/79/reviews",
+        ] {
+            server
+                .mock("GET", path)
+                .match_query(mockito::Matcher::Any)
+                .with_body("[]")
+                .create_async()
+                .await;
+        }
+        let summary = server
+            .mock("POST", "/repos/fresheyes/bitcoin-fork/issues/5/comments")
+            .match_body(mockito::Matcher::Regex(
+                "There was 1 comment left by 1 reviewer.*fresheyes:reveal".to_string(),
+            ))
+            .with_status(201)
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+        let on_line = server
+            .mock("POST", "/repos/fresheyes/bitcoin-fork/pulls/5/comments")
+            .match_body(mockito::Matcher::PartialJson(
+                json!({"path": "src/main.cpp", "line": 10, "side": "RIGHT"}),
+            ))
+            .with_status(201)
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+        let not_in_diff = server
+            .mock("POST", "/repos/fresheyes/bitcoin-fork/pulls/5/comments")
+            .match_body(mockito::Matcher::PartialJson(json!({"path": "src/gone.cpp"})))
+            .with_status(422)
+            .with_body(r#"{"message": "Validation Failed", "errors": [{"resource": "PullRequestReviewComment", "code": "custom", "message": "pull_request_review_thread.path is invalid"}]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let fallback = server
+            .mock("POST", "/repos/fresheyes/bitcoin-fork/issues/5/comments")
+            .match_body(mockito::Matcher::Regex(
+                "On `src/gone.cpp` line 1:".to_string(),
+            ))
+            .with_status(201)
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let result = reveal(&client, "bitcoin", "bitcoin", 79, ResolvedThreads::Show)
+            .await
+            .unwrap();
+
+        summary.assert_async().await;
+        on_line.assert_async().await;
+        not_in_diff.assert_async().await;
+        fallback.assert_async().await;
+        assert!(!result.already_revealed);
+        assert_eq!(
+            result.mirror_pr_url,
+            "https://github.com/fresheyes/bitcoin-fork/pull/5"
+        );
+        assert_eq!(result.lines.len(), 2);
+        assert_eq!(
+            (result.lines[0].path.as_str(), result.lines[0].on_line),
+            ("src/main.cpp", true)
+        );
+        assert_eq!(
+            (result.lines[1].path.as_str(), result.lines[1].on_line),
+            ("src/gone.cpp", false)
+        );
+
+        // a second reveal finds the summary and posts nothing
+        let mut server = mockito::Server::new_async().await;
+        mock_mirror(
+            &mut server,
+            json!([{"id": 1, "body": "There were no comments\n\n<!-- fresheyes:reveal -->", "html_url": "https://github.com/fresheyes/bitcoin-fork/pull/5#issuecomment-1", "created_at": "2024-01-02T00:00:00Z", "user": {"login": "fresheyes"}}]),
+        )
+        .await;
+        let client = test_client(&server);
+
+        let result = reveal(&client, "bitcoin", "bitcoin", 79, ResolvedThreads::Show)
+            .await
+            .unwrap();
+
+        assert!(result.already_revealed);
+        assert!(result.lines.is_empty());
+
+        // nothing to reveal on without a mirror
+        let mut server = mockito::Server::new_async().await;
+        mock_existing_fork(&mut server).await;
+        server
+            .mock("GET", "/repos/fresheyes/bitcoin-fork/pulls")
+            .match_query(mockito::Matcher::Any)
+            .with_body("[]")
+            .create_async()
+            .await;
+        let client = test_client(&server);
+
+        let result = reveal(&client, "bitcoin", "bitcoin", 80, ResolvedThreads::Show).await;
+
+        assert!(matches!(result, Err(FreshEyesError::MirrorNotFound(_))));
+    }
+}
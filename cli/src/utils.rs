@@ -1,39 +1,73 @@
-use std::{env, fs, io::stdin, path::Path, sync::Arc};
+use std::{collections::HashMap, env, fs, io::stdin, path::PathBuf, sync::Arc};
 
 use once_cell::sync::Lazy;
 use tokio::sync::Mutex;
 
-static TOKEN: Lazy<Arc<Mutex<Option<String>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+use crate::client::{normalize_host, GITHUB_HOST};
 
-/// Gets the GitHub personal access token from the user, or prompts them to create one if it doesn't exist.
-pub async fn get_or_prompt_token() -> Result<String, std::io::Error> {
-    let mut token = TOKEN.lock().await;
+/// Tokens already entered in this session, by host
+static TOKENS: Lazy<Arc<Mutex<HashMap<String, String>>>> =
+    Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
 
-    if let Some(ref t) = *token {
+/// Gets the GitHub personal access token for the given host from the user, or prompts them to create one if it doesn't exist.
+pub async fn get_or_prompt_token(host: &str) -> Result<String, std::io::Error> {
+    let host = normalize_host(host)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string()))?;
+    let mut tokens = TOKENS.lock().await;
+
+    if let Some(t) = tokens.get(&host) {
         Ok(t.clone())
     } else {
-        let new_token = get_token()?;
-        *token = Some(new_token.clone());
+        let new_token = get_token(&host)?;
+        tokens.insert(host, new_token.clone());
         Ok(new_token)
     }
 }
 
-fn get_token() -> Result<String, std::io::Error> {
+/// The environment variable with the token of a host, `GITHUB_TOKEN` for github.com and e.g.
+/// `GITHUB_TOKEN_GITHUB_EXAMPLE_COM` for github.example.com, so no host gets the token of another
+pub(crate) fn token_env_var(host: &str) -> String {
+    if host == GITHUB_HOST {
+        "GITHUB_TOKEN".to_string()
+    } else {
+        let host: String = host
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!("GITHUB_TOKEN_{}", host)
+    }
+}
+
+/// The token of github.com is read from `GITHUB_TOKEN` or `~/.fresheyes/fresheyes`,
+/// tokens of GitHub Enterprise Server hosts from `GITHUB_TOKEN_<HOST>` or `~/.fresheyes/hosts/<host>`
+fn get_token(host: &str) -> Result<String, std::io::Error> {
     // Check if the environment variable for the token exists
-    if let Ok(env_token) = env::var("GITHUB_TOKEN") {
-        return Ok(format!("Bearer {}", env_token));
+    if let Ok(env_token) = env::var(token_env_var(host)) {
+        return Ok(env_token);
     }
 
     let home = env::var("HOME").unwrap();
-    let config_dir = format!("{}/.fresheyes", home);
-    fs::create_dir_all(&config_dir)?;
+    let config_dir = PathBuf::from(format!("{}/.fresheyes", home));
+    let config_path = if host == GITHUB_HOST {
+        config_dir.join("fresheyes")
+    } else {
+        config_dir.join("hosts").join(host)
+    };
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
 
-    let config_path = Path::new(&config_dir).join("fresheyes");
     if config_path.exists() {
         let config = fs::read_to_string(config_path)?;
-        Ok(format!("Bearer {}", config))
+        Ok(config.trim().to_string())
     } else {
-        let token = prompt_for_token();
+        let token = prompt_for_token(host);
 
         println!("Would you like to save this token for future use? [y/N]");
         let mut save_token = String::new();
@@ -42,29 +76,33 @@ fn get_token() -> Result<String, std::io::Error> {
             "y" => {
                 fs::write(&config_path, &token)?;
                 println!(
-                    "Token saved! You can delete it at any time by deleting the file at ~/.fresheyes/fresheyes"
+                    "Token saved! You can delete it at any time by deleting the file at {}",
+                    config_path.display()
                 );
-                Ok(format!("Bearer {}", token))
+                Ok(token)
             }
             "n" => {
                 println!(
                     "Token not saved. You will be prompted for it again next time you run fresheyes."
                 );
-                Ok(format!("Bearer {}", token))
+                Ok(token)
             }
             _ => {
                 println!("Invalid input. Token not saved.");
-                return Err(std::io::Error::new(
+                Err(std::io::Error::new(
                     std::io::ErrorKind::InvalidInput,
                     "Invalid input",
-                ));
+                ))
             }
         }
     }
 }
 
-fn prompt_for_token() -> String {
-    println!("Please enter your GitHub personal access token:");
+fn prompt_for_token(host: &str) -> String {
+    println!(
+        "Please enter your GitHub personal access token for {}:",
+        host
+    );
     let mut token = String::new();
     stdin().read_line(&mut token).unwrap();
     token.trim().to_string()